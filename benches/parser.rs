@@ -0,0 +1,38 @@
+//! `cargo bench --features bench --bench parser`
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+
+/// SIMD分类的解析器和逐字节解析器, 按行解析
+fn bench_parser(c: &mut Criterion) {
+    let fixed_width = include_str!("../tests/corpus/fixed_width.txt");
+    // 字段之间有很长的空格
+    let wide = [
+        "6224      ABC20200902       1312       1145       7802       5411          17800          90532                                           1109.2049 ",
+        "9951      EFG0990     132230     132280     102230     192230          4         46     5938.6 561969.6111",
+    ]
+    .repeat(500)
+    .join("\n");
+
+    let mut group = c.benchmark_group("parser");
+    for (input_name, input) in [("fixed_width", fixed_width), ("wide", &wide)] {
+        let lines: Vec<&str> = input.lines().collect();
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        for (name, parse) in [
+            ("simd", zsan::bench::parse as fn(&[u8]) -> usize),
+            ("scalar", zsan::bench::parse_scalar),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, input_name), &lines, |b, lines| {
+                b.iter(|| {
+                    lines
+                        .iter()
+                        .map(|line| parse(line.as_bytes()))
+                        .sum::<usize>()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(parser_benches, bench_parser);
+criterion_main!(parser_benches);
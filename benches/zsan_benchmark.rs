@@ -1,5 +1,5 @@
-use criterion::{Criterion, criterion_group, criterion_main};
-use zsan::{compress, decompress};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use zsan::{Compressor, compress, compress_entropy, decompress};
 
 fn bench_compress(c: &mut Criterion) {
     let test_cases = [
@@ -44,11 +44,212 @@ fn bench_decompress(c: &mut Criterion) {
         c.bench_function(&format!("decompress_{}", name), |b| {
             b.iter(|| {
                 let mut output = Vec::new();
-                decompress(&compressed_data, &mut output);
+                decompress(&compressed_data, &mut output).unwrap();
             })
         });
     }
 }
 
-criterion_group!(zsan_benches, bench_compress, bench_decompress);
+/// 生成定长格式的行情记录, 用固定种子的LCG保证每次运行语料一致
+fn fixed_width_corpus(lines: usize) -> String {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move |modulo: u64| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) % modulo
+    };
+    (0..lines)
+        .map(|_| {
+            format!(
+                "{:<10}{}{:08} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}.{} {:>8}.{:04}",
+                6000 + next(4000),
+                ["ABC", "EFG", "XYZ"][next(3) as usize],
+                20200000 + next(10000),
+                130000 + next(5000),
+                130000 + next(5000),
+                100000 + next(5000),
+                190000 + next(5000),
+                next(100),
+                next(1000),
+                next(10000),
+                next(10),
+                next(1000000),
+                next(10000),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn bench_entropy(c: &mut Criterion) {
+    let corpus = fixed_width_corpus(1000);
+
+    let mut plain = Vec::new();
+    compress(&corpus, &mut plain);
+    let mut entropy = Vec::new();
+    compress_entropy(&corpus, &mut entropy);
+    let optimal_compressor = Compressor::builder().optimal_parse(true).build();
+    let mut optimal = Vec::new();
+    optimal_compressor.compress(&corpus, &mut optimal);
+    // 大小放在断言信息里, 熵编码和选模式都不应该比默认的大
+    assert!(
+        entropy.len() < plain.len() && optimal.len() <= plain.len(),
+        "fixed-width corpus: {} bytes, zsan {} bytes, zsan+huffman {} bytes, zsan optimal parse {} bytes",
+        corpus.len(),
+        plain.len(),
+        entropy.len(),
+        optimal.len()
+    );
+
+    c.bench_function("compress_corpus", |b| {
+        b.iter(|| {
+            let mut output = Vec::new();
+            compress(&corpus, &mut output);
+        })
+    });
+    c.bench_function("compress_entropy_corpus", |b| {
+        b.iter(|| {
+            let mut output = Vec::new();
+            compress_entropy(&corpus, &mut output);
+        })
+    });
+    c.bench_function("compress_optimal_parse_corpus", |b| {
+        b.iter(|| {
+            let mut output = Vec::new();
+            optimal_compressor.compress(&corpus, &mut output);
+        })
+    });
+    c.bench_function("decompress_corpus", |b| {
+        b.iter(|| {
+            let mut output = Vec::new();
+            decompress(&plain, &mut output).unwrap();
+        })
+    });
+    c.bench_function("decompress_entropy_corpus", |b| {
+        b.iter(|| {
+            let mut output = Vec::new();
+            decompress(&entropy, &mut output).unwrap();
+        })
+    });
+}
+
+/// 解码吞吐量, 按原文字节数计算
+fn bench_decompress_throughput(c: &mut Criterion) {
+    let corpus = fixed_width_corpus(1000);
+    let mut group = c.benchmark_group("decompress_throughput");
+    group.throughput(Throughput::Bytes(corpus.len() as u64));
+    for (name, compressor) in [
+        ("plain", Compressor::default()),
+        ("entropy", Compressor::builder().entropy(true).build()),
+    ] {
+        let mut compressed = Vec::new();
+        compressor.compress(&corpus, &mut compressed);
+        group.bench_with_input(name, &compressed, |b, compressed| {
+            let mut output = Vec::with_capacity(corpus.len());
+            b.iter(|| {
+                output.clear();
+                decompress(compressed, &mut output).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+/// tests/corpus 下的语料, 每行是一条记录
+const CORPUS: &[(&str, &str)] = &[
+    (
+        "fixed_width",
+        include_str!("../tests/corpus/fixed_width.txt"),
+    ),
+    ("csv", include_str!("../tests/corpus/csv.txt")),
+    ("log", include_str!("../tests/corpus/log.txt")),
+    ("numeric", include_str!("../tests/corpus/numeric.txt")),
+];
+
+/// 逐行压缩和解压语料的吞吐量, 按原文字节数计算
+fn bench_corpus_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("corpus_compress");
+    for (name, text) in CORPUS {
+        let lines: Vec<&str> = text.lines().collect();
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(*name, &lines, |b, lines| {
+            let mut output = Vec::with_capacity(text.len());
+            b.iter(|| {
+                output.clear();
+                for line in lines {
+                    compress(line, &mut output);
+                }
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("corpus_decompress");
+    for (name, text) in CORPUS {
+        let records: Vec<Vec<u8>> = text
+            .lines()
+            .map(|line| {
+                let mut compressed = Vec::new();
+                compress(line, &mut compressed);
+                compressed
+            })
+            .collect();
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(*name, &records, |b, records| {
+            let mut output = Vec::with_capacity(text.len());
+            b.iter(|| {
+                output.clear();
+                for record in records {
+                    decompress(record, &mut output).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+/// 换一种编码方式: 直接转码和先解压再压缩
+fn bench_transcode(c: &mut Criterion) {
+    let corpus = fixed_width_corpus(1000);
+    let mut plain = Vec::new();
+    compress(&corpus, &mut plain);
+    let mut entropy = Vec::new();
+    compress_entropy(&corpus, &mut entropy);
+    let target = Compressor::builder().checksum(true).build();
+
+    let mut group = c.benchmark_group("transcode");
+    group.throughput(Throughput::Bytes(corpus.len() as u64));
+    for (name, source) in [("plain", &plain), ("entropy", &entropy)] {
+        group.bench_with_input(BenchmarkId::new("transcode", name), source, |b, source| {
+            b.iter(|| {
+                let mut output = Vec::new();
+                target.transcode(source, &mut output).unwrap();
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("decompress_compress", name),
+            source,
+            |b, source| {
+                b.iter(|| {
+                    let mut text = Vec::new();
+                    decompress(source, &mut text).unwrap();
+                    let mut output = Vec::new();
+                    target.compress(std::str::from_utf8(&text).unwrap(), &mut output);
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    zsan_benches,
+    bench_compress,
+    bench_decompress,
+    bench_entropy,
+    bench_decompress_throughput,
+    bench_corpus_throughput,
+    bench_transcode
+);
 criterion_main!(zsan_benches);
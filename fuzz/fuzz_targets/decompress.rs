@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// 任意字节都不能导致panic, 出错时不能输出任何内容
+fuzz_target!(|data: &[u8]| {
+    let mut out = Vec::new();
+    if zsan::decompress(data, &mut out).is_err() {
+        assert!(out.is_empty());
+    }
+});
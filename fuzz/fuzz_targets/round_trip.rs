@@ -0,0 +1,35 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// 第一个字节选择压缩选项, 其余部分作为ASCII文本
+fuzz_target!(|data: &[u8]| {
+    let Some((&options, text)) = data.split_first() else {
+        return;
+    };
+    let Ok(text) = std::str::from_utf8(text) else {
+        return;
+    };
+    if !text.is_ascii() {
+        return;
+    }
+
+    let compressor = zsan::Compressor::builder()
+        .optimal_parse(options & 0b_0000_0001 != 0)
+        .entropy(options & 0b_0000_0010 != 0)
+        .checksum(options & 0b_0000_0100 != 0)
+        .tie_break(if options & 0b_0000_1000 != 0 {
+            zsan::TieBreak::Token
+        } else {
+            zsan::TieBreak::Literal
+        })
+        .min_space_run(((options >> 4) & 0b_0000_0011) as usize + 1)
+        .max_decimal_places(if options & 0b_0100_0000 != 0 { 2 } else { 15 })
+        .build();
+    let mut compressed = Vec::new();
+    compressor.compress(text, &mut compressed);
+
+    let mut out = Vec::new();
+    zsan::decompress(&compressed, &mut out).unwrap();
+    assert_eq!(out, text.as_bytes());
+});
@@ -1,4 +1,5 @@
 pub mod decimal;
+mod format;
 pub mod integer;
 pub mod space;
 pub mod unsigned_decimal;
@@ -21,3 +22,43 @@ pub(super) fn is_space(b: u8) -> bool {
 pub(super) fn is_numerical(b: u8) -> bool {
     b & HOLDER_MASK == NUMERICAL_HOLDER_FLAG
 }
+
+/// 数字token解码出的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Number {
+    pub value: u64,
+    pub negative: bool,
+    pub decimal_places: u8,
+}
+
+impl Number {
+    /// 文本的字节数, 不需要写出文本
+    #[inline]
+    pub fn text_len(&self) -> usize {
+        self.negative as usize + format::number_len(self.value, self.decimal_places)
+    }
+
+    #[inline]
+    pub fn write(&self, out: &mut Vec<u8>) {
+        if self.negative {
+            out.push(b'-');
+        }
+        format::write_number(self.value, self.decimal_places, out);
+    }
+}
+
+/// 数字的文本形式, 和 `retrave_blocks` 解析的输入一致
+#[cfg(test)]
+pub(super) fn format_number(val: u64, negative: bool, decimal_places: u8) -> String {
+    let sign = if negative { "-" } else { "" };
+    if decimal_places == 0 {
+        return format!("{sign}{val}");
+    }
+    let scale = 10_u64.pow(decimal_places as u32);
+    format!(
+        "{sign}{}.{:0width$}",
+        val / scale,
+        val % scale,
+        width = decimal_places as usize
+    )
+}
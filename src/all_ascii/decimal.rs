@@ -3,12 +3,9 @@ const DECIMAL_FLAG: u8 = 0b_0001_0000;
 
 /// 最高两位是11, 代表这个位置是无符号整数，剩余的6个bit和后续字节表示整数值，整数值采用变长编码,
 /// 例如: 64个空格会用0b_1011_1111和0b_1000_0001两个字节表示。
-/// return true if the integer is compressed, false otherwise.
+/// return false if the value cannot be represented in this mode.
+/// 是否比字面量更短由 `Compressor` 决定。
 pub fn compress_decimal(val: u64, negative: bool, decimal_places: u8, out: &mut Vec<u8>) -> bool {
-    if (!negative && decimal_places == 0) && val < 100 {
-        return false;
-    }
-
     let first_byte_msb = super::NUMERICAL_HOLDER_FLAG
         | if negative { NEGATIVE_FLAG } else { 0 }
         | if decimal_places > 0 { DECIMAL_FLAG } else { 0 };
@@ -30,10 +27,15 @@ pub fn compress_decimal(val: u64, negative: bool, decimal_places: u8, out: &mut
 }
 
 #[inline]
-pub fn decompress_decimal(input: &[u8], out: &mut Vec<u8>) -> usize {
-    if input[0] & NEGATIVE_FLAG != 0 {
-        out.push(b'-');
-    }
+pub fn decompress_decimal(input: &[u8], out: &mut Vec<u8>) -> Option<usize> {
+    let (number, len) = decode_decimal(input)?;
+    number.write(out);
+    Some(len)
+}
+
+#[inline]
+pub fn decode_decimal(input: &[u8]) -> Option<(super::Number, usize)> {
+    let negative = input[0] & NEGATIVE_FLAG != 0;
 
     let mut size = 0;
 
@@ -50,39 +52,27 @@ pub fn decompress_decimal(input: &[u8], out: &mut Vec<u8>) -> usize {
 
     // let (mut value, len) = decode_fn(input);
 
-    let (mut value, decimal_places, len) = if input[0] & DECIMAL_FLAG != 0 {
+    let (value, decimal_places, len) = if input[0] & DECIMAL_FLAG != 0 {
         size += 1;
-        let (value, len) = crate::vle_variants::decode_0(&input[1..]);
+        let (value, len) = crate::vle_variants::decode_0(&input[1..])?;
         (value, input[0] & 0b_0000_1111, len)
     } else {
-        let (value, len) = crate::vle_variants::decode_4(input);
+        let (value, len) = crate::vle_variants::decode_4(input)?;
         (value, 0_u8, len)
     };
 
-    const MAX_LEN: usize = 20;
-    let mut buf = [b'0'; MAX_LEN];
-    let mut index = MAX_LEN - 1;
-    while value > 0 {
-        let digit = (value % 10) as u8;
-        buf[index] = b'0' + digit;
-        value /= 10;
-        index -= 1;
-    }
-    if decimal_places > 0 {
-        let integer_end = MAX_LEN - decimal_places as usize;
-        let integer_start = (index + 1).min(integer_end - 1);
-        out.extend_from_slice(&buf[integer_start..integer_end]);
-        out.push(b'.');
-        out.extend_from_slice(&buf[integer_end..]);
-    } else {
-        out.extend_from_slice(&buf[index + 1..]);
-    }
-
-    size + len
+    let number = super::Number {
+        value,
+        negative,
+        decimal_places,
+    };
+    Some((number, size + len))
 }
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
     #[test]
     fn test_integer_positive() {
         for x in 1..1000 {
@@ -123,4 +113,27 @@ mod test {
             }
         }
     }
+
+    proptest! {
+        #[test]
+        fn round_trip(
+            val in any::<u64>(),
+            negative in any::<bool>(),
+            decimal_places in 0..=15_u8,
+            tail in prop::collection::vec(any::<u8>(), 0..4),
+        ) {
+            let mut out = Vec::new();
+            prop_assert!(super::compress_decimal(val, negative, decimal_places, &mut out));
+            let len = out.len();
+            // 解码只读取token本身
+            out.extend_from_slice(&tail);
+            let mut final_out = Vec::new();
+            prop_assert_eq!(super::decompress_decimal(&out, &mut final_out), Some(len));
+            let expected = super::super::format_number(val, negative, decimal_places);
+            prop_assert_eq!(final_out, expected.into_bytes());
+            if len > 1 {
+                prop_assert_eq!(super::decompress_decimal(&out[..len - 1], &mut Vec::new()), None);
+            }
+        }
+    }
 }
@@ -0,0 +1,102 @@
+/// "00" 到 "99", 每次查表写两位
+const DIGIT_PAIRS: &[u8; 200] = b"\
+    0001020304050607080910111213141516171819\
+    2021222324252627282930313233343536373839\
+    4041424344454647484950515253545556575859\
+    6061626364656667686970717273747576777879\
+    8081828384858687888990919293949596979899";
+
+/// 十进制位数, 0 的位数是 0
+#[inline]
+fn digit_count(value: u64) -> usize {
+    value.checked_ilog10().map_or(0, |log| log as usize + 1)
+}
+
+/// 从右往左写满 `buf`, 调用者保证 `buf` 已经填好 `'0'` 并且放得下 `value`
+#[inline]
+fn write_digits(mut value: u64, buf: &mut [u8]) {
+    let mut end = buf.len();
+    while value >= 100 {
+        let pair = (value % 100) as usize * 2;
+        value /= 100;
+        buf[end - 2..end].copy_from_slice(&DIGIT_PAIRS[pair..pair + 2]);
+        end -= 2;
+    }
+    if value >= 10 {
+        let pair = value as usize * 2;
+        buf[end - 2..end].copy_from_slice(&DIGIT_PAIRS[pair..pair + 2]);
+    } else if value > 0 {
+        buf[end - 1] = b'0' + value as u8;
+    }
+}
+
+/// [`write_number`] 写出的字节数
+#[inline]
+pub(super) fn number_len(value: u64, decimal_places: u8) -> usize {
+    let places = decimal_places as usize;
+    let digits = digit_count(value);
+    if places == 0 {
+        digits
+    } else {
+        digits.saturating_sub(places).max(1) + 1 + places
+    }
+}
+
+/// 把 `value` 写成十进制追加到 `out`, 最后 `decimal_places` 位是小数。
+///
+/// 小数前至少有一位整数, 小数部分不足时补0, 例如 `5` 保留两位小数是 `0.05`。
+/// 没有小数时 0 不输出任何字符, 编码时不会产生这样的token。
+#[inline]
+pub(super) fn write_number(value: u64, decimal_places: u8, out: &mut Vec<u8>) {
+    let places = decimal_places as usize;
+    let digits = digit_count(value);
+    let start = out.len();
+    if places == 0 {
+        out.resize(start + digits, b'0');
+        write_digits(value, &mut out[start..]);
+        return;
+    }
+
+    let integer_len = digits.saturating_sub(places).max(1);
+    out.resize(start + integer_len + 1 + places, b'0');
+    let (integer, fraction) = out[start..].split_at_mut(integer_len);
+    let scale = 10_u64.pow(places as u32);
+    write_digits(value / scale, integer);
+    fraction[0] = b'.';
+    write_digits(value % scale, &mut fraction[1..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    fn write(value: u64, decimal_places: u8) -> String {
+        let mut out = b"-".to_vec();
+        super::write_number(value, decimal_places, &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_number() {
+        assert_eq!(write(0, 0), "-");
+        assert_eq!(write(7, 0), "-7");
+        assert_eq!(write(1234, 0), "-1234");
+        assert_eq!(write(u64::MAX, 0), "-18446744073709551615");
+        assert_eq!(write(5, 2), "-0.05");
+        assert_eq!(write(0, 3), "-0.000");
+        assert_eq!(write(1234, 2), "-12.34");
+        assert_eq!(write(u64::MAX, 15), "-18446.744073709551615");
+    }
+
+    proptest! {
+        #[test]
+        fn matches_reference(value in any::<u64>(), decimal_places in 0..=15_u8) {
+            prop_assume!(value != 0 || decimal_places != 0);
+            let mut out = Vec::new();
+            super::write_number(value, decimal_places, &mut out);
+            prop_assert_eq!(out.len(), super::number_len(value, decimal_places));
+            let expected = super::super::format_number(value, false, decimal_places);
+            prop_assert_eq!(out, expected.into_bytes());
+        }
+    }
+}
@@ -2,9 +2,10 @@ const NEGATIVE_FLAG: u8 = 0b_0010_0000;
 
 /// 最高两位是11, 代表这个位置是无符号整数，剩余的6个bit和后续字节表示整数值，整数值采用变长编码,
 /// 例如: 64个空格会用0b_1011_1111和0b_1000_0001两个字节表示。
-/// return true if the integer is compressed, false otherwise.
-pub fn compress_integer(val: u64, negative: bool, _decimal_places: u8, out: &mut Vec<u8>) -> bool {
-    if !negative && (val < 10 || (val > 31 && val < 100)) {
+/// return false if the value cannot be represented in this mode.
+/// 是否比字面量更短由 `Compressor` 决定。
+pub fn compress_integer(val: u64, negative: bool, decimal_places: u8, out: &mut Vec<u8>) -> bool {
+    if decimal_places > 0 {
         return false;
     }
 
@@ -16,30 +17,30 @@ pub fn compress_integer(val: u64, negative: bool, _decimal_places: u8, out: &mut
 }
 
 #[inline]
-pub fn decompress_integer(input: &[u8], out: &mut Vec<u8>) -> usize {
-    if input[0] & NEGATIVE_FLAG != 0 {
-        out.push(b'-');
-    }
-
-    let (mut value, len) = crate::vle_variants::decode_5(input);
+pub fn decompress_integer(input: &[u8], out: &mut Vec<u8>) -> Option<usize> {
+    let (number, len) = decode_integer(input)?;
+    number.write(out);
+    Some(len)
+}
 
-    const MAX_LEN: usize = 20;
-    let mut buf = [0u8; MAX_LEN];
-    let mut index = MAX_LEN - 1;
-    while value > 0 {
-        let digit = (value % 10) as u8;
-        buf[index] = b'0' + digit;
-        value /= 10;
-        index -= 1;
-    }
+#[inline]
+pub fn decode_integer(input: &[u8]) -> Option<(super::Number, usize)> {
+    let negative = input[0] & NEGATIVE_FLAG != 0;
 
-    out.extend_from_slice(&buf[index + 1..]);
+    let (value, len) = crate::vle_variants::decode_5(input)?;
 
-    len
+    let number = super::Number {
+        value,
+        negative,
+        decimal_places: 0,
+    };
+    Some((number, len))
 }
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
     #[test]
     fn test_integer_positive() {
         for x in 1..1000 {
@@ -66,4 +67,27 @@ mod test {
             }
         }
     }
+
+    proptest! {
+        #[test]
+        fn round_trip(
+            val in 1..=u64::MAX,
+            negative in any::<bool>(),
+            decimal_places in Just(0_u8),
+            tail in prop::collection::vec(any::<u8>(), 0..4),
+        ) {
+            let mut out = Vec::new();
+            prop_assert!(super::compress_integer(val, negative, decimal_places, &mut out));
+            let len = out.len();
+            // 解码只读取token本身
+            out.extend_from_slice(&tail);
+            let mut final_out = Vec::new();
+            prop_assert_eq!(super::decompress_integer(&out, &mut final_out), Some(len));
+            let expected = super::super::format_number(val, negative, decimal_places);
+            prop_assert_eq!(final_out, expected.into_bytes());
+            if len > 1 {
+                prop_assert_eq!(super::decompress_integer(&out[..len - 1], &mut Vec::new()), None);
+            }
+        }
+    }
 }
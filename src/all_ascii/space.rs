@@ -5,10 +5,6 @@ const SPACE_OUNT_MASK: u8 = 0b_0011_1111; // 63
 /// 例如: 64个空格会用0b_1011_1111和0b_1000_0001两个字节表示。
 ///
 pub fn compress_space(count: usize, out: &mut Vec<u8>) {
-    if count == 1 {
-        out.push(b' ');
-        return;
-    }
     let current_out_len = out.len();
 
     // encode the rest
@@ -25,6 +21,12 @@ pub fn compress_space(count: usize, out: &mut Vec<u8>) {
     }
 }
 
+/// 空格token代表的空格数
+#[inline]
+pub fn space_len(input: u8) -> usize {
+    (input & SPACE_OUNT_MASK) as usize
+}
+
 #[inline]
 pub fn decompress_space(input: u8, out: &mut Vec<u8>) -> usize {
     // MSB is 11, it's a space count byte
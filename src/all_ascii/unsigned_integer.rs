@@ -1,13 +1,14 @@
 /// 最高两位是11, 代表这个位置是无符号整数，剩余的6个bit和后续字节表示整数值，整数值采用变长编码,
 /// 例如: 64个空格会用0b_1011_1111和0b_1000_0001两个字节表示。
-/// return true if the integer is compressed, false otherwise.
+/// return false if the value cannot be represented in this mode.
+/// 是否比字面量更短由 `Compressor` 决定。
 pub fn compress_unsigned_integer(
     val: u64,
-    _negative: bool,
-    _decimal_places: u8,
+    negative: bool,
+    decimal_places: u8,
     out: &mut Vec<u8>,
 ) -> bool {
-    if val < 10 || (val > 31 && val < 100) {
+    if negative || decimal_places > 0 {
         return false;
     }
 
@@ -19,25 +20,28 @@ pub fn compress_unsigned_integer(
 }
 
 #[inline]
-pub fn decompress_unsigned_integer(input: &[u8], out: &mut Vec<u8>) -> usize {
-    let (mut value, len) = crate::vle_variants::decode_6(input);
-
-    const MAX_LEN: usize = 20;
-    let mut buf = [0u8; MAX_LEN];
-    let mut index = MAX_LEN - 1;
-    while value > 0 {
-        let digit = (value % 10) as u8;
-        buf[index] = b'0' + digit;
-        value /= 10;
-        index -= 1;
-    }
-    out.extend_from_slice(&buf[index + 1..]);
+pub fn decompress_unsigned_integer(input: &[u8], out: &mut Vec<u8>) -> Option<usize> {
+    let (number, len) = decode_unsigned_integer(input)?;
+    number.write(out);
+    Some(len)
+}
 
-    len
+#[inline]
+pub fn decode_unsigned_integer(input: &[u8]) -> Option<(super::Number, usize)> {
+    let (value, len) = crate::vle_variants::decode_6(input)?;
+
+    let number = super::Number {
+        value,
+        negative: false,
+        decimal_places: 0,
+    };
+    Some((number, len))
 }
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
     #[test]
     fn test_unsigned_integer() {
         for x in 1..1000 {
@@ -51,4 +55,27 @@ mod test {
             }
         }
     }
+
+    proptest! {
+        #[test]
+        fn round_trip(
+            val in 1..=u64::MAX,
+            negative in Just(false),
+            decimal_places in Just(0_u8),
+            tail in prop::collection::vec(any::<u8>(), 0..4),
+        ) {
+            let mut out = Vec::new();
+            prop_assert!(super::compress_unsigned_integer(val, negative, decimal_places, &mut out));
+            let len = out.len();
+            // 解码只读取token本身
+            out.extend_from_slice(&tail);
+            let mut final_out = Vec::new();
+            prop_assert_eq!(super::decompress_unsigned_integer(&out, &mut final_out), Some(len));
+            let expected = super::super::format_number(val, negative, decimal_places);
+            prop_assert_eq!(final_out, expected.into_bytes());
+            if len > 1 {
+                prop_assert_eq!(super::decompress_unsigned_integer(&out[..len - 1], &mut Vec::new()), None);
+            }
+        }
+    }
 }
@@ -0,0 +1,48 @@
+/// CRC-32C (Castagnoli) 多项式, 反射形式
+const POLY: u32 = 0x82f6_3b78;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32C of `input`.
+pub fn crc32c(input: &[u8]) -> u32 {
+    crc32c_update(0, input)
+}
+
+/// 增量计算: `crc32c_update(crc32c(a), b) == crc32c(a ++ b)`
+pub fn crc32c_update(crc: u32, input: &[u8]) -> u32 {
+    !input.iter().fold(!crc, |crc, &b| {
+        TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(super::crc32c(b""), 0);
+        assert_eq!(super::crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(super::crc32c(&[0_u8; 32]), 0x8a91_36aa);
+        assert_eq!(
+            super::crc32c_update(super::crc32c(b"1234"), b"56789"),
+            0xe306_9283
+        );
+    }
+}
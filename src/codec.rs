@@ -0,0 +1,197 @@
+//! `tokio-util` codec for zsan frames, enabled with the `tokio` feature.
+
+use std::io;
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::Compressor;
+use crate::vle_variants::decode_0;
+
+/// varint长度最多占用的字节数
+const MAX_LEN_BYTES: usize = 10;
+
+/// Encodes each `&str` as one length-prefixed zsan frame, the same format as
+/// [`Compressor::compress_frames`], and decodes frames back to `String`s.
+///
+/// ```no_run
+/// use futures_util::{SinkExt, StreamExt};
+/// use tokio_util::codec::Framed;
+///
+/// # async fn run(socket: tokio::net::TcpStream) -> std::io::Result<()> {
+/// let mut framed = Framed::new(socket, zsan::ZsanCodec::new());
+/// framed.send("6224      ABC20200902       1312").await?;
+/// while let Some(record) = framed.next().await {
+///     println!("{}", record?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Malformed frames, frames longer than [`ZsanCodec::max_frame_len`] and records
+/// that do not decode to UTF-8 are reported as [`io::ErrorKind::InvalidData`].
+#[derive(Debug, Clone)]
+pub struct ZsanCodec {
+    compressor: Compressor,
+    max_frame_len: usize,
+}
+
+impl Default for ZsanCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZsanCodec {
+    /// A codec using the default [`Compressor`] and frames of at most 8 MiB.
+    pub fn new() -> Self {
+        Self::with_compressor(Compressor::default())
+    }
+
+    pub fn with_compressor(compressor: Compressor) -> Self {
+        ZsanCodec {
+            compressor,
+            max_frame_len: 8 << 20,
+        }
+    }
+
+    /// Longest compressed frame accepted by the decoder, protects against
+    /// allocating for a corrupt length prefix.
+    pub fn max_frame_len(mut self, len: usize) -> Self {
+        self.max_frame_len = len;
+        self
+    }
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+impl Encoder<&str> for ZsanCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: &str, dst: &mut BytesMut) -> Result<(), io::Error> {
+        let mut frame = Vec::new();
+        crate::frame::compress_frame(&self.compressor, item, &mut frame);
+        dst.extend_from_slice(&frame);
+        Ok(())
+    }
+}
+
+impl Decoder for ZsanCodec {
+    type Item = String;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<String>, io::Error> {
+        // 长度还没有收完
+        let prefix = &src[..src.len().min(MAX_LEN_BYTES)];
+        if prefix.iter().all(|&b| b & 0b_1000_0000 != 0) {
+            return if prefix.len() < MAX_LEN_BYTES {
+                Ok(None)
+            } else {
+                Err(invalid_data("invalid frame length"))
+            };
+        }
+
+        let (len, header_len) =
+            decode_0(src).ok_or_else(|| invalid_data("invalid frame length"))?;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= self.max_frame_len)
+            .ok_or_else(|| invalid_data(format!("frame of {len} bytes exceeds the limit")))?;
+        if src.len() < header_len + len {
+            src.reserve(header_len + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(header_len);
+        let payload = src.split_to(len);
+        let mut text = Vec::new();
+        crate::decompress(&payload, &mut text).map_err(invalid_data)?;
+        String::from_utf8(text).map(Some).map_err(invalid_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZsanCodec;
+    use crate::Compressor;
+    use futures_util::{SinkExt, StreamExt};
+    use std::io;
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
+
+    fn records() -> Vec<String> {
+        (0..200)
+            .map(|i| {
+                format!(
+                    "{:<10}ABC{:08}   {:>8}  -{}.{:02}",
+                    6000 + i,
+                    i * 7919,
+                    i * 31,
+                    i,
+                    i % 100
+                )
+            })
+            .chain([String::new(), "价格 123".to_owned()])
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_duplex() {
+        let (client, server) = tokio::io::duplex(64);
+        let codec = ZsanCodec::with_compressor(Compressor::builder().checksum(true).build());
+
+        let writer = tokio::spawn(async move {
+            let mut framed = FramedWrite::new(client, codec);
+            for record in records() {
+                framed.send(record.as_str()).await.unwrap();
+            }
+        });
+
+        let received: Vec<String> = FramedRead::new(server, ZsanCodec::new())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        writer.await.unwrap();
+        assert_eq!(received, records());
+    }
+
+    #[test]
+    fn test_partial_frames() {
+        let mut codec = ZsanCodec::new();
+        let mut encoded = bytes::BytesMut::new();
+        for record in records() {
+            codec.encode(record.as_str(), &mut encoded).unwrap();
+        }
+
+        // 每次只收到一个字节
+        let mut buf = bytes::BytesMut::new();
+        let mut received = Vec::new();
+        for &b in encoded.iter() {
+            buf.extend_from_slice(&[b]);
+            while let Some(record) = codec.decode(&mut buf).unwrap() {
+                received.push(record);
+            }
+        }
+        assert!(buf.is_empty());
+        assert_eq!(received, records());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_frames() {
+        for (input, limit) in [
+            // 0x7F不是合法的token
+            (&[3, 0x40, b'A', 0x7f][..], 1024),
+            (&[0xff; 10][..], 1024),
+            (&[0x80, 0x01][..], 100),
+        ] {
+            let (mut client, server) = tokio::io::duplex(64);
+            client.write_all(input).await.unwrap();
+            drop(client);
+            let mut framed = FramedRead::new(server, ZsanCodec::new().max_frame_len(limit));
+            let error = framed.next().await.unwrap().unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{input:?}");
+        }
+    }
+}
@@ -0,0 +1,300 @@
+/// What to emit when a token would be as long as the literal text it replaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    /// Keep the literal, which the decoder only has to copy.
+    #[default]
+    Literal,
+    /// Emit the token.
+    Token,
+}
+
+/// A configurable zsan compressor, created with [`Compressor::builder`].
+///
+/// The options only decide which spaces and numbers become tokens. The decoder never
+/// needs them: everything it has to know is in the header byte and the tokens, so the
+/// output of any `Compressor` is read back with [`crate::decompress`].
+///
+/// ```
+/// let compressor = zsan::Compressor::builder()
+///     .min_space_run(4)
+///     .decimals(false)
+///     .build();
+/// let mut out = Vec::new();
+/// compressor.compress("A    1.25  B", &mut out);
+///
+/// let mut text = Vec::new();
+/// zsan::decompress(&out, &mut text).unwrap();
+/// assert_eq!(text, b"A    1.25  B");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compressor {
+    pub(crate) min_space_run: usize,
+    pub(crate) min_number_len: usize,
+    pub(crate) spaces: bool,
+    pub(crate) integers: bool,
+    pub(crate) negatives: bool,
+    pub(crate) decimals: bool,
+    pub(crate) max_decimal_places: u8,
+    pub(crate) tie_break: TieBreak,
+    pub(crate) entropy: bool,
+    pub(crate) optimal_parse: bool,
+    pub(crate) checksum: bool,
+    pub(crate) frame_size: usize,
+    pub(crate) order_preserving: bool,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self {
+            min_space_run: 2,
+            min_number_len: 1,
+            spaces: true,
+            integers: true,
+            negatives: true,
+            decimals: true,
+            max_decimal_places: crate::zsan_parser::MAX_DECIMAL_PLACES as u8,
+            tie_break: TieBreak::Literal,
+            entropy: false,
+            optimal_parse: false,
+            checksum: false,
+            frame_size: 1 << 20,
+            order_preserving: false,
+        }
+    }
+}
+
+impl Compressor {
+    pub fn builder() -> CompressorBuilder {
+        CompressorBuilder {
+            compressor: Compressor::default(),
+        }
+    }
+
+    /// Compress `src` and append the result to `out`, see [`crate::compress`].
+    pub fn compress(&self, src: &str, out: &mut Vec<u8>) {
+        crate::zsan::compress_with(self, src, out);
+    }
+
+    /// Compress bytes that need not be UTF-8, like [`Compressor::compress`].
+    /// [`crate::decompress`] gives back the same bytes.
+    ///
+    /// ```
+    /// let mut compressed = Vec::new();
+    /// zsan::Compressor::default().compress_bytes(b"\xff   1312", &mut compressed);
+    /// let mut bytes = Vec::new();
+    /// zsan::decompress(&compressed, &mut bytes).unwrap();
+    /// assert_eq!(bytes, b"\xff   1312");
+    /// ```
+    pub fn compress_bytes(&self, src: &[u8], out: &mut Vec<u8>) {
+        crate::zsan::compress_bytes(self, src, out);
+    }
+
+    /// 数字块是否可以编码成token
+    pub(crate) fn accepts_number(
+        &self,
+        size: usize,
+        negative: bool,
+        decimal_places: usize,
+    ) -> bool {
+        size >= self.min_number_len
+            && if decimal_places > 0 {
+                self.decimals && decimal_places <= self.max_decimal_places as usize
+            } else {
+                self.integers
+            }
+            && (self.negatives || !negative)
+    }
+
+    /// 空格块是否可以编码成token
+    pub(crate) fn accepts_space(&self, size: usize) -> bool {
+        self.spaces && size >= self.min_space_run
+    }
+
+    /// token是否比字面量更好
+    pub(crate) fn prefers_token(&self, token_len: usize, literal_len: usize) -> bool {
+        token_len < literal_len || (token_len == literal_len && self.tie_break == TieBreak::Token)
+    }
+}
+
+/// Builder for [`Compressor`].
+#[derive(Debug, Clone)]
+pub struct CompressorBuilder {
+    compressor: Compressor,
+}
+
+impl CompressorBuilder {
+    /// Shortest run of spaces that is replaced by a space token, 2 by default.
+    pub fn min_space_run(mut self, len: usize) -> Self {
+        self.compressor.min_space_run = len.max(1);
+        self
+    }
+
+    /// Shortest number (including sign and decimal point) that is replaced by a token, 1 by default.
+    pub fn min_number_len(mut self, len: usize) -> Self {
+        self.compressor.min_number_len = len.max(1);
+        self
+    }
+
+    /// Whether runs of spaces are encoded as tokens.
+    pub fn spaces(mut self, enabled: bool) -> Self {
+        self.compressor.spaces = enabled;
+        self
+    }
+
+    /// Whether numbers without a decimal point are encoded as tokens.
+    pub fn integers(mut self, enabled: bool) -> Self {
+        self.compressor.integers = enabled;
+        self
+    }
+
+    /// Whether negative numbers are encoded as tokens.
+    pub fn negatives(mut self, enabled: bool) -> Self {
+        self.compressor.negatives = enabled;
+        self
+    }
+
+    /// Whether numbers with a decimal point are encoded as tokens.
+    pub fn decimals(mut self, enabled: bool) -> Self {
+        self.compressor.decimals = enabled;
+        self
+    }
+
+    /// Numbers with more decimal places stay literal. The format stores at most 15.
+    pub fn max_decimal_places(mut self, places: u8) -> Self {
+        self.compressor.max_decimal_places =
+            places.min(crate::zsan_parser::MAX_DECIMAL_PLACES as u8);
+        self
+    }
+
+    /// What to emit when a token is exactly as long as the literal text.
+    pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.compressor.tie_break = tie_break;
+        self
+    }
+
+    /// Huffman code the token stream, see [`crate::compress_entropy`].
+    pub fn entropy(mut self, enabled: bool) -> Self {
+        self.compressor.entropy = enabled;
+        self
+    }
+
+    /// Find the shortest token stream instead of tokenizing the numbers and runs of
+    /// spaces as parsed.
+    ///
+    /// Every number the text contains is a candidate token, e.g. also a prefix of a
+    /// longer number, a run of more than 18 digits, or a number without its sign.
+    /// For every encoding mode a dynamic program over the record picks tokens or
+    /// literals by their exact encoded size, and the mode with the shortest result
+    /// wins. The token stream is never longer than without this option. The other
+    /// options still decide which tokens are allowed, and the tie-break which one
+    /// is used at equal size.
+    ///
+    /// This is tens of times slower than the default, since every digit starts
+    /// candidates in every mode. Tokens may then cover only a
+    /// part of a number, which [`crate::numbers`] and [`crate::CompressedMatcher`]
+    /// handle by decoding the tokens around it.
+    pub fn optimal_parse(mut self, enabled: bool) -> Self {
+        self.compressor.optimal_parse = enabled;
+        self
+    }
+
+    /// Append a CRC-32C of the original text, verified by [`crate::decompress`].
+    /// Costs 5 bytes per record.
+    pub fn checksum(mut self, enabled: bool) -> Self {
+        self.compressor.checksum = enabled;
+        self
+    }
+
+    /// Minimum size of a frame in bytes, 1 MiB by default. Frames end at the first
+    /// `\n` after this size, see [`Compressor::compress_frames`].
+    pub fn frame_size(mut self, bytes: usize) -> Self {
+        self.compressor.frame_size = bytes.max(1);
+        self
+    }
+
+    /// Encode records so that comparing them byte by byte gives the same order as
+    /// comparing their texts, e.g. to use them as keys of a sorted store.
+    ///
+    /// Runs of spaces and digits are still shortened, but numbers are encoded by
+    /// their digits and not by value, because `"9"` sorts after `"10"` as text. The
+    /// result is larger than the default encoding and may be larger than the text.
+    /// [`entropy`](Self::entropy) and [`checksum`](Self::checksum) are ignored, and
+    /// every record decodes with [`crate::decompress`].
+    ///
+    /// ```
+    /// let compressor = zsan::Compressor::builder().order_preserving(true).build();
+    /// let encode = |text: &str| {
+    ///     let mut out = Vec::new();
+    ///     compressor.compress(text, &mut out);
+    ///     out
+    /// };
+    /// assert!(encode("ABC  10") < encode("ABC  9"));
+    /// assert!(encode("ABC  9") < encode("ABC 1"));
+    /// ```
+    pub fn order_preserving(mut self, enabled: bool) -> Self {
+        self.compressor.order_preserving = enabled;
+        self
+    }
+
+    pub fn build(self) -> Compressor {
+        self.compressor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compressor, TieBreak};
+
+    fn round_trip(compressor: &Compressor, input: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        compressor.compress(input, &mut out);
+        let mut final_out = Vec::new();
+        crate::decompress(&out, &mut final_out).unwrap();
+        assert_eq!(String::from_utf8(final_out).unwrap(), input);
+        out
+    }
+
+    #[test]
+    fn test_default_matches_baseline() {
+        // 加入选项之前的 `compress` 的输出
+        let input = "6224      ABC20200902       1312       -1145       7802.5 ";
+        let baseline = [
+            0x43, 0xc8, 0x8a, 0x06, 0x86, 0x41, 0x42, 0x43, 0xce, 0xb8, 0x8f, 0x9a, 0x01, 0x87,
+            0xc8, 0xa4, 0x01, 0x87, 0xe9, 0x8f, 0x01, 0x87, 0xd1, 0xc9, 0xe1, 0x04, 0x20,
+        ];
+        assert_eq!(round_trip(&Compressor::default(), input), baseline);
+    }
+
+    #[test]
+    fn test_token_types() {
+        let input = "A    1234  -1234  12.3456789  B";
+        let all = round_trip(&Compressor::default(), input).len();
+        for compressor in [
+            Compressor::builder().spaces(false).build(),
+            Compressor::builder().integers(false).build(),
+            Compressor::builder().negatives(false).build(),
+            Compressor::builder().decimals(false).build(),
+            Compressor::builder().max_decimal_places(1).build(),
+            Compressor::builder().min_space_run(3).build(),
+            Compressor::builder().min_number_len(6).build(),
+        ] {
+            assert!(round_trip(&compressor, input).len() > all, "{compressor:?}");
+        }
+    }
+
+    #[test]
+    fn test_tie_break() {
+        // 两位数和一个空格编码后长度与字面量相同
+        let input = "A 99 B   123456";
+        let literal = Compressor::builder().min_space_run(1).build();
+        let token = Compressor::builder()
+            .min_space_run(1)
+            .tie_break(TieBreak::Token)
+            .build();
+        let literal_out = round_trip(&literal, input);
+        let token_out = round_trip(&token, input);
+        assert!(literal_out[1..].starts_with(b"A 99 B"));
+        assert_eq!(literal_out.len(), token_out.len());
+        assert_ne!(literal_out, token_out);
+    }
+}
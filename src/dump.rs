@@ -0,0 +1,264 @@
+//! Annotated listing of a compressed record, for debugging.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::checksum::crc32c_update;
+use crate::header::*;
+use crate::zsan::Record;
+use crate::{Token, Tokens};
+
+/// 每行最多显示的原始字节数
+const MAX_RAW_BYTES: usize = 8;
+
+/// Lists the header and every token of a compressed record, one per line.
+///
+/// ```
+/// let mut compressed = Vec::new();
+/// zsan::compress("ABC      -1312.25", &mut compressed);
+/// print!("{}", zsan::ZsanDump(&compressed));
+/// ```
+///
+/// prints
+///
+/// ```text
+/// header 0x43: version 0, decimal mode
+/// offset  text    kind     raw bytes                       value
+/// 0       0       literal  41 42 43                        "ABC"
+/// 3       3       spaces   86                              6
+/// 4       9       number   f2 99 81 08                     -1312.25
+/// 17 bytes of text
+/// ```
+///
+/// `offset` is the position in the token stream, as in
+/// [`ZsanError::InvalidToken`](crate::ZsanError::InvalidToken), and `text` the
+/// position of the token's text in the decompressed record. Malformed input is
+/// listed up to the error, which ends the dump. Records written with
+/// [`CompressorBuilder::order_preserving`](crate::CompressorBuilder::order_preserving)
+/// have no tokens, only their size and text length are shown.
+#[derive(Debug, Clone, Copy)]
+pub struct ZsanDump<'a>(pub &'a [u8]);
+
+/// [`ZsanDump`] as a `String`.
+pub fn dump(input: &[u8]) -> String {
+    ZsanDump(input).to_string()
+}
+
+impl fmt::Display for ZsanDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let input = self.0;
+        let Some(&first_byte) = input.first() else {
+            return writeln!(f, "empty record");
+        };
+        write!(f, "header {first_byte:#04x}: ")?;
+        let record = match Record::parse(input) {
+            Ok(record) => record,
+            Err(e) => return writeln!(f, "error: {e}"),
+        };
+        let header = &record.header;
+        write_header(f, header)?;
+        if header.features & ORDERED_FEATURE != 0 {
+            // `Record::parse` 已经还原出原文, 没有可以列出的token
+            let coded = input.len() - header.len - record.checksum.map_or(0, |_| CHECKSUM_LEN);
+            writeln!(f, "ordered encoding not shown, {coded} bytes")?;
+            let text = &record.payload[..];
+            return write_summary(f, text.len(), crc32c_update(0, text), record.checksum);
+        }
+        if header.entropy {
+            let coded = input.len() - header.len - record.checksum.map_or(0, |_| CHECKSUM_LEN);
+            writeln!(
+                f,
+                "huffman coded: {coded} bytes, {} bytes of tokens",
+                record.payload.len()
+            )?;
+        }
+
+        writeln!(
+            f,
+            "{:<8}{:<8}{:<9}{:<32}value",
+            "offset", "text", "kind", "raw bytes"
+        )?;
+        let stream = &record.payload[..];
+        let mut tokens = Tokens::from_stream(Cow::Borrowed(stream), header).peekable();
+        let mut text_len = 0;
+        let mut crc = 0;
+        let mut text = Vec::new();
+        while let Some(token) = tokens.next() {
+            let (offset, token) = match token {
+                Ok(token) => token,
+                Err(e) => return writeln!(f, "error: {e}"),
+            };
+            let end = match tokens.peek() {
+                Some(Ok((next, _)) | Err(crate::ZsanError::InvalidToken(next))) => *next,
+                Some(Err(_)) | None => stream.len(),
+            };
+            let (kind, value) = match &token {
+                Token::Literal(bytes) => ("literal", format!("\"{}\"", bytes.escape_ascii())),
+                Token::Spaces(count) => ("spaces", count.to_string()),
+                Token::Number { .. } => ("number", String::new()),
+            };
+            text.clear();
+            token.write(&mut text);
+            let value = if value.is_empty() {
+                String::from_utf8_lossy(&text).into_owned()
+            } else {
+                value
+            };
+            writeln!(
+                f,
+                "{offset:<8}{text_len:<8}{kind:<9}{:<31} {value}",
+                raw_bytes(&stream[offset..end])
+            )?;
+            text_len += text.len();
+            crc = crc32c_update(crc, &text);
+        }
+
+        write_summary(f, text_len, crc, record.checksum)
+    }
+}
+
+/// 原文的长度和校验和
+fn write_summary(
+    f: &mut fmt::Formatter<'_>,
+    text_len: usize,
+    crc: u32,
+    checksum: Option<u32>,
+) -> fmt::Result {
+    writeln!(f, "{text_len} bytes of text")?;
+    if let Some(expected) = checksum {
+        if expected == crc {
+            writeln!(f, "checksum {expected:#010x} ok")?;
+        } else {
+            writeln!(
+                f,
+                "checksum {expected:#010x} mismatch, the text has {crc:#010x}"
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn write_header(f: &mut fmt::Formatter<'_>, header: &Header) -> fmt::Result {
+    write!(f, "version {FORMAT_VERSION}, ")?;
+    if header.features & ORDERED_FEATURE != 0 {
+        write!(f, "order preserving")?;
+    } else if header.stored {
+        write!(f, "stored")?;
+    } else {
+        let mode = match header.first_byte & ENCODE_MODE_MASK {
+            FIRST_BYTE_UNSIGNED_INTEGER => "unsigned integer",
+            FIRST_BYTE_INTEGER => "integer",
+            FIRST_BYTE_UNSIGNED_DECIMAL => "unsigned decimal",
+            _ => "decimal",
+        };
+        write!(f, "{mode} mode")?;
+    }
+    if header.features & CHECKSUM_FEATURE != 0 {
+        write!(f, ", checksum")?;
+    }
+    writeln!(f)
+}
+
+/// 十六进制, 太长时只显示开头
+fn raw_bytes(bytes: &[u8]) -> String {
+    let mut out = bytes
+        .iter()
+        .take(MAX_RAW_BYTES)
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if bytes.len() > MAX_RAW_BYTES {
+        out.push_str(&format!(" +{}", bytes.len() - MAX_RAW_BYTES));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dump;
+    use crate::Compressor;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_dump() {
+        let mut compressed = Vec::new();
+        crate::compress("ABC      -1312.25", &mut compressed);
+        assert_eq!(
+            dump(&compressed),
+            "\
+header 0x43: version 0, decimal mode
+offset  text    kind     raw bytes                       value
+0       0       literal  41 42 43                        \"ABC\"
+3       3       spaces   86                              6
+4       9       number   f2 99 81 08                     -1312.25
+17 bytes of text
+"
+        );
+
+        let mut compressed = Vec::new();
+        Compressor::builder()
+            .checksum(true)
+            .entropy(true)
+            .build()
+            .compress(&"ABCDEFGHIJ  12 ".repeat(10), &mut compressed);
+        let listing = dump(&compressed);
+        assert!(listing.starts_with("header 0xc4: version 0, unsigned integer mode, checksum\n"));
+        assert!(listing.contains("\nhuffman coded: "));
+        assert!(listing.contains("\"ABCDEFGHIJ\"\n"));
+        assert!(listing.contains(" +2 "));
+        assert!(listing.contains("\n150 bytes of text\nchecksum 0x"));
+        assert!(listing.ends_with(" ok\n"));
+
+        *compressed.last_mut().unwrap() ^= 1;
+        assert!(dump(&compressed).contains(" mismatch, the text has 0x"));
+
+        let mut compressed = Vec::new();
+        Compressor::builder()
+            .order_preserving(true)
+            .build()
+            .compress("A  12", &mut compressed);
+        assert_eq!(
+            dump(&compressed),
+            "\
+header 0xc0: version 0, order preserving
+ordered encoding not shown, 5 bytes
+5 bytes of text
+"
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(dump(&[]), "empty record\n");
+        assert_eq!(
+            dump(b"1"),
+            "header 0x31: error: invalid header byte 0b00110001\n"
+        );
+        assert_eq!(
+            dump(&[0x48, 0xff]),
+            "\
+header 0x48: version 0, stored
+offset  text    kind     raw bytes                       value
+0       0       literal  ff                              \"\\xff\"
+1 bytes of text
+"
+        );
+        assert_eq!(
+            dump(&[0x40, b'A', 0x7f]),
+            "\
+header 0x40: version 0, unsigned integer mode
+offset  text    kind     raw bytes                       value
+0       0       literal  41                              \"A\"
+error: invalid token at offset 1
+"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn any_bytes_never_panic(input in prop::collection::vec(any::<u8>(), 0..64)) {
+            let listing = dump(&input);
+            prop_assert!(listing.ends_with('\n'));
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Canonical Huffman coding used as the optional second stage over the zsan token stream.
+//!
+//! Layout of an entropy coded payload:
+//!
+//! | field          | size                 | description                                      |
+//! |----------------|----------------------|--------------------------------------------------|
+//! | `len`          | varint (`encode_0`)  | length of the decoded token stream               |
+//! | `symbol_count` | 1 byte               | number of distinct symbols minus one             |
+//! | `symbols`      | `symbol_count` bytes | the symbols in ascending order                   |
+//! | `lengths`      | `ceil(count / 2)`    | code length of each symbol, two nibbles per byte |
+//! | `bits`         | rest                 | MSB first, the last byte is padded with zeros    |
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// 码长上限, 保证码长能放进一个 nibble.
+const MAX_CODE_LEN: u8 = 15;
+
+/// Entropy code `input` and append the payload to `out`.
+pub fn encode(input: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(&crate::vle_variants::encode_0(input.len() as u64));
+    if input.is_empty() {
+        return;
+    }
+
+    let mut freqs = [0_u64; 256];
+    for &b in input {
+        freqs[b as usize] += 1;
+    }
+    let lengths = code_lengths(&freqs);
+
+    let symbols: Vec<u8> = (0..=u8::MAX).filter(|&s| lengths[s as usize] > 0).collect();
+    out.push((symbols.len() - 1) as u8);
+    out.extend_from_slice(&symbols);
+    for pair in symbols.chunks(2) {
+        let high = lengths[pair[0] as usize] << 4;
+        let low = pair.get(1).map_or(0, |&s| lengths[s as usize]);
+        out.push(high | low);
+    }
+
+    let codes = canonical_codes(&lengths);
+    let mut acc: u64 = 0;
+    let mut acc_bits = 0;
+    for &b in input {
+        let len = lengths[b as usize] as u32;
+        acc = (acc << len) | codes[b as usize] as u64;
+        acc_bits += len;
+        while acc_bits >= 8 {
+            acc_bits -= 8;
+            out.push((acc >> acc_bits) as u8);
+        }
+    }
+    if acc_bits > 0 {
+        out.push((acc << (8 - acc_bits)) as u8);
+    }
+}
+
+/// Decode a payload produced by [`encode`] and append the token stream to `out`.
+/// return false if the payload is malformed.
+pub fn decode(input: &[u8], out: &mut Vec<u8>) -> bool {
+    let Some((len, mut index)) = crate::vle_variants::decode_0(input) else {
+        return false;
+    };
+    let len = len as usize;
+    if len == 0 {
+        return true;
+    }
+
+    let Some(&count) = input.get(index) else {
+        return false;
+    };
+    let count = count as usize + 1;
+    index += 1;
+    let Some(symbols) = input.get(index..index + count) else {
+        return false;
+    };
+    index += count;
+    let Some(packed) = input.get(index..index + count.div_ceil(2)) else {
+        return false;
+    };
+    index += count.div_ceil(2);
+
+    // counts[n]: 码长为n的符号个数, sorted: 按(码长, 符号)排序的符号
+    let mut counts = [0_u16; MAX_CODE_LEN as usize + 1];
+    let mut entries = Vec::with_capacity(count);
+    for (i, &symbol) in symbols.iter().enumerate() {
+        let nibble = if i % 2 == 0 {
+            packed[i / 2] >> 4
+        } else {
+            packed[i / 2] & 0b_0000_1111
+        };
+        if nibble == 0 {
+            return false;
+        }
+        counts[nibble as usize] += 1;
+        entries.push((nibble, symbol));
+    }
+    entries.sort_unstable();
+    let sorted: Vec<u8> = entries.into_iter().map(|(_, symbol)| symbol).collect();
+
+    let bits = &input[index..];
+    // 每个符号至少占一位, 防止伪造的长度导致超大的内存分配
+    if len > bits.len().saturating_mul(8) {
+        return false;
+    }
+    let mut bit_pos = 0;
+    out.reserve(len);
+    for _ in 0..len {
+        let mut code: u32 = 0;
+        let mut first: u32 = 0;
+        let mut offset: u32 = 0;
+        let mut symbol = None;
+        for &count in counts.iter().skip(1) {
+            let Some(&byte) = bits.get(bit_pos / 8) else {
+                return false;
+            };
+            code |= ((byte >> (7 - bit_pos % 8)) & 1) as u32;
+            bit_pos += 1;
+            let count = count as u32;
+            if code < first + count {
+                symbol = Some(sorted[(offset + code - first) as usize]);
+                break;
+            }
+            offset += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        match symbol {
+            Some(s) => out.push(s),
+            None => return false,
+        }
+    }
+    true
+}
+
+/// 计算每个符号的码长, 超过 `MAX_CODE_LEN` 时把频率减半后重新构建.
+fn code_lengths(freqs: &[u64; 256]) -> [u8; 256] {
+    let mut freqs = *freqs;
+    loop {
+        let lengths = huffman_lengths(&freqs);
+        if lengths.iter().all(|&l| l <= MAX_CODE_LEN) {
+            return lengths;
+        }
+        for f in freqs.iter_mut().filter(|f| **f > 0) {
+            *f = f.div_ceil(2);
+        }
+    }
+}
+
+fn huffman_lengths(freqs: &[u64; 256]) -> [u8; 256] {
+    let mut lengths = [0_u8; 256];
+    // parents[i]: 节点i的父节点, 前256个是叶子
+    let mut parents = vec![usize::MAX; 256];
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> = freqs
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| **f > 0)
+        .map(|(s, &f)| Reverse((f, s)))
+        .collect();
+
+    if heap.len() == 1 {
+        let Reverse((_, s)) = heap.pop().unwrap();
+        lengths[s] = 1;
+        return lengths;
+    }
+
+    while heap.len() > 1 {
+        let Reverse((w1, n1)) = heap.pop().unwrap();
+        let Reverse((w2, n2)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[n1] = node;
+        parents[n2] = node;
+        heap.push(Reverse((w1 + w2, node)));
+    }
+
+    for (s, length) in lengths.iter_mut().enumerate() {
+        if freqs[s] == 0 {
+            continue;
+        }
+        let mut depth = 0_usize;
+        let mut node = s;
+        while parents[node] != usize::MAX {
+            node = parents[node];
+            depth += 1;
+        }
+        *length = depth.min(u8::MAX as usize) as u8;
+    }
+    lengths
+}
+
+/// 按(码长, 符号)顺序分配范式哈夫曼编码.
+fn canonical_codes(lengths: &[u8; 256]) -> [u32; 256] {
+    let mut codes = [0_u32; 256];
+    let mut code: u32 = 0;
+    for len in 1..=MAX_CODE_LEN {
+        for s in 0..256 {
+            if lengths[s] == len {
+                codes[s] = code;
+                code += 1;
+            }
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+#[cfg(test)]
+mod tests {
+    fn round_trip(input: &[u8]) -> usize {
+        let mut encoded = Vec::new();
+        super::encode(input, &mut encoded);
+        let mut decoded = Vec::new();
+        assert!(super::decode(&encoded, &mut decoded));
+        assert_eq!(decoded, input);
+        encoded.len()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(b"");
+        round_trip(b"a");
+        round_trip(b"aaaaaaaaaaaaaaaaaaaa");
+        round_trip(b"ABC\xc5\x8a\xbf\xbfEFG\x81");
+        let all: Vec<u8> = (0..=u8::MAX).collect();
+        round_trip(&all);
+    }
+
+    #[test]
+    fn test_skewed() {
+        // fibonacci 频率会生成超过15位的码长, 需要限制码长
+        let mut input = Vec::new();
+        let (mut a, mut b) = (1, 1);
+        for s in 0..24_u8 {
+            input.extend(std::iter::repeat_n(s, a));
+            (a, b) = (b, a + b);
+        }
+        let size = round_trip(&input);
+        assert!(size < input.len() / 2);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut encoded = Vec::new();
+        super::encode(b"hello world", &mut encoded);
+        for len in 0..encoded.len() {
+            let mut decoded = Vec::new();
+            assert!(!super::decode(&encoded[..len], &mut decoded));
+        }
+    }
+}
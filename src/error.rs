@@ -0,0 +1,45 @@
+use std::fmt;
+
+/// Errors returned when reading compressed data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZsanError {
+    /// The input ended in the middle of the header or a token.
+    Truncated,
+    /// The first byte is not a zsan header.
+    InvalidHeader(u8),
+    /// The data was written by a newer format version.
+    UnsupportedVersion(u8),
+    /// The data uses an extension feature this version does not know.
+    UnsupportedFeature(u8),
+    /// The token at this offset of the token stream is malformed.
+    InvalidToken(usize),
+    /// The Huffman coded token stream is malformed.
+    InvalidEntropyStream,
+    /// The checksum stored in the record does not match the decoded text.
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for ZsanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZsanError::Truncated => write!(f, "compressed data is truncated"),
+            ZsanError::InvalidHeader(b) => write!(f, "invalid header byte {b:#010b}"),
+            ZsanError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported format version {v}, this decoder supports version {}",
+                crate::FORMAT_VERSION
+            ),
+            ZsanError::UnsupportedFeature(bit) => {
+                write!(f, "unsupported extension feature bit {bit}")
+            }
+            ZsanError::InvalidToken(offset) => write!(f, "invalid token at offset {offset}"),
+            ZsanError::InvalidEntropyStream => write!(f, "invalid entropy coded stream"),
+            ZsanError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch, expected {expected:#010x} but the text has {actual:#010x}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ZsanError {}
@@ -0,0 +1,159 @@
+//! Length-prefixed frames for inputs made of many records.
+//!
+//! A frame is the length of its payload as a varint (7 bits per byte, least
+//! significant group first, bit 7 set when another byte follows) followed by the
+//! payload, which is a complete compressed record as written by
+//! [`Compressor::compress`]. Every frame decodes on its own, so large inputs can be
+//! compressed and decompressed in pieces, see [`Compressor::compress_frames`].
+//!
+//! The input is split after the first `\n` at or past [`CompressorBuilder::frame_size`]
+//! bytes. The split only depends on the text and the frame size, so the frames are
+//! the same no matter how many threads produce them.
+//!
+//! [`CompressorBuilder::frame_size`]: crate::CompressorBuilder::frame_size
+
+use crate::vle_variants::{decode_0, encode_0};
+use crate::{Compressor, ZsanError};
+
+/// 以换行为边界把输入切成不小于 `frame_size` 的块, 每块包含结尾的换行
+pub(crate) fn split_frames(src: &str, frame_size: usize) -> impl Iterator<Item = &str> {
+    let mut rest = src;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.as_bytes()[frame_size.min(rest.len())..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(rest.len(), |i| frame_size + i + 1);
+        let (frame, tail) = rest.split_at(end);
+        rest = tail;
+        Some(frame)
+    })
+}
+
+/// 压缩一帧并在前面加上长度
+pub(crate) fn compress_frame(options: &Compressor, src: &str, out: &mut Vec<u8>) {
+    let mut payload = Vec::with_capacity(src.len() / 2);
+    crate::zsan::compress_with(options, src, &mut payload);
+    out.extend_from_slice(&encode_0(payload.len() as u64));
+    out.extend_from_slice(&payload);
+}
+
+/// 读出下一帧, 返回帧的内容和帧占用的总长度
+pub(crate) fn next_frame(input: &[u8]) -> Result<(&[u8], usize), ZsanError> {
+    let (len, header_len) = decode_0(input).ok_or(ZsanError::Truncated)?;
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| len.checked_add(header_len))
+        .filter(|&end| end <= input.len())
+        .ok_or(ZsanError::Truncated)?;
+    Ok((&input[header_len..end], end))
+}
+
+/// Decompress a sequence of frames written by [`Compressor::compress_frames`] and
+/// append the text to `out`.
+///
+/// Nothing is appended to `out` when an error is returned.
+pub fn decompress_frames(input: &[u8], out: &mut Vec<u8>) -> Result<(), ZsanError> {
+    let start = out.len();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let result = next_frame(rest)
+            .and_then(|(payload, len)| crate::decompress(payload, out).map(|_| len));
+        match result {
+            Ok(len) => rest = &rest[len..],
+            Err(e) => {
+                out.truncate(start);
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Compressor {
+    /// Split `src` at line boundaries into frames of about
+    /// [`frame_size`](crate::CompressorBuilder::frame_size) bytes and append the
+    /// compressed frames to `out`, see [`decompress_frames`].
+    pub fn compress_frames(&self, src: &str, out: &mut Vec<u8>) {
+        for frame in split_frames(src, self.frame_size) {
+            compress_frame(self, frame, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(count: usize) -> String {
+        (0..count)
+            .map(|i| {
+                format!(
+                    "{i:>8} ABC{:08}      {}.{:02}\n",
+                    i * 7919,
+                    i % 1000,
+                    i % 100
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_frames() {
+        let src = lines(100);
+        let frames: Vec<&str> = split_frames(&src, 256).collect();
+        assert_eq!(frames.concat(), src);
+        for frame in &frames {
+            assert!(frame.len() >= 256 || frame == frames.last().unwrap());
+            assert!(frame.ends_with('\n'));
+        }
+
+        // 没有换行时整个输入是一帧
+        assert_eq!(
+            split_frames("ABC   123", 4).collect::<Vec<_>>(),
+            ["ABC   123"]
+        );
+        assert_eq!(split_frames("", 4).count(), 0);
+    }
+
+    #[test]
+    fn test_frames_round_trip() {
+        let src = lines(1000);
+        for compressor in [
+            Compressor::builder().frame_size(1).build(),
+            Compressor::builder().frame_size(4096).build(),
+            Compressor::builder()
+                .frame_size(4096)
+                .entropy(true)
+                .checksum(true)
+                .build(),
+        ] {
+            let mut out = Vec::new();
+            compressor.compress_frames(&src, &mut out);
+            assert!(out.len() < src.len());
+            let mut text = Vec::new();
+            decompress_frames(&out, &mut text).unwrap();
+            assert_eq!(text, src.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_truncated_frames() {
+        let src = lines(100);
+        let mut out = Vec::new();
+        Compressor::builder()
+            .frame_size(256)
+            .build()
+            .compress_frames(&src, &mut out);
+        for len in [1, out.len() / 2, out.len() - 1] {
+            let mut text = b"kept".to_vec();
+            assert_eq!(
+                decompress_frames(&out[..len], &mut text),
+                Err(ZsanError::Truncated)
+            );
+            assert_eq!(text, b"kept");
+        }
+    }
+}
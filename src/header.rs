@@ -0,0 +1,191 @@
+//! The header in front of every compressed record.
+//!
+//! First byte:
+//!
+//! | bit | name             | description                                                |
+//! |-----|------------------|------------------------------------------------------------|
+//! | 7   | `EXTENSION_FLAG` | extension bytes follow the first byte                      |
+//! | 6   | `ZSAN_FLAG_MASK` | always 1                                                   |
+//! | 5-4 | `VERSION_MASK`   | format version, currently [`FORMAT_VERSION`] (0)           |
+//! | 3   | `STORED_FLAG`    | the input follows as is, bits 2-0 must be 0                |
+//! | 2   | `ENTROPY_FLAG`   | the token stream is Huffman coded, see `crate::entropy`    |
+//! | 1-0 | mode             | how numerical tokens are encoded, `FIRST_BYTE_*`           |
+//!
+//! Extension bytes: bit 7 set means another extension byte follows, bits 6-0 are
+//! feature bits. The first extension byte carries features 0-6, the second 7-13 and
+//! so on.
+//!
+//! | feature | name               | description                                                    |
+//! |---------|--------------------|----------------------------------------------------------------|
+//! | 0       | `CHECKSUM_FEATURE` | CRC-32C of the original text, 4 bytes little endian at the end |
+//! | 1       | `ORDERED_FEATURE`  | order-preserving encoding, see `crate::ordered`; bits 3-0 of the first byte must be 0 |
+//!
+//! Compatibility rules:
+//!
+//! - Anything written with version 0 decodes the same with every later release.
+//!   `tests/golden.rs` freezes the exact bytes of the current format.
+//! - A new version or a new feature bit is only introduced together with the
+//!   decoder that understands it. Decoders return [`ZsanError::UnsupportedVersion`]
+//!   or [`ZsanError::UnsupportedFeature`] instead of guessing.
+
+use crate::ZsanError;
+
+pub(crate) const EXTENSION_FLAG: u8 = 0b_1000_0000;
+
+pub(crate) const ZSAN_FLAG_MASK: u8 = 0b_0100_0000;
+
+pub(crate) const VERSION_MASK: u8 = 0b_0011_0000;
+pub(crate) const VERSION_SHIFT: u8 = 4;
+/// The format version this crate writes. [`crate::decompress`] refuses any other version.
+pub const FORMAT_VERSION: u8 = 0;
+
+/// 原样存储: 首字节之后就是原文, 编码结果不比原文短或者原文包含无法作为字面量的字节时使用
+pub(crate) const STORED_FLAG: u8 = 0b_0000_1000;
+
+/// token流经过了哈夫曼编码, 见 `compress_entropy`
+pub(crate) const ENTROPY_FLAG: u8 = 0b_0000_0100;
+
+/// 00:方式1:所有的Numerical都是正整数
+pub(crate) const FIRST_BYTE_UNSIGNED_INTEGER: u8 = 0b_0000_0000;
+/// 01:方式2:如果所有的Numerical都是整数但是包含负数
+pub(crate) const FIRST_BYTE_INTEGER: u8 = 0b_0000_0010;
+
+/// 10:方式3:如果所有的Numerical都是正数但是包含小数
+pub(crate) const FIRST_BYTE_UNSIGNED_DECIMAL: u8 = 0b_0000_0001;
+/// 11:方式4:所有的Numerical包含负数和小数
+pub(crate) const FIRST_BYTE_DECIMAL: u8 = 0b_0000_0011;
+
+pub(crate) const ENCODE_MODE_MASK: u8 = 0b_0000_0011;
+
+pub(crate) const NEGATIVE_FLAG: u8 = 0b_0000_0010;
+pub(crate) const DECIMAL_FLAG: u8 = 0b_0000_0001;
+
+const EXTENSION_MORE_FLAG: u8 = 0b_1000_0000;
+const EXTENSION_FEATURE_MASK: u8 = 0b_0111_1111;
+
+/// 记录末尾有原文的CRC-32C
+pub(crate) const CHECKSUM_FEATURE: u64 = 1 << 0;
+pub(crate) const CHECKSUM_LEN: usize = 4;
+
+/// 保序编码, 见 `crate::ordered`
+pub(crate) const ORDERED_FEATURE: u64 = 1 << 1;
+
+/// 当前版本认识的扩展特性
+const KNOWN_FEATURES: u64 = CHECKSUM_FEATURE | ORDERED_FEATURE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Header {
+    /// 首字节, 包含编码方式
+    pub first_byte: u8,
+    pub stored: bool,
+    pub entropy: bool,
+    /// 扩展字节里的特性位
+    pub features: u64,
+    /// 头部占用的字节数
+    pub len: usize,
+}
+
+impl Header {
+    pub fn parse(input: &[u8]) -> Result<Header, ZsanError> {
+        let Some(&first_byte) = input.first() else {
+            return Err(ZsanError::Truncated);
+        };
+        if first_byte & ZSAN_FLAG_MASK == 0 {
+            return Err(ZsanError::InvalidHeader(first_byte));
+        }
+        let version = (first_byte & VERSION_MASK) >> VERSION_SHIFT;
+        if version != FORMAT_VERSION {
+            return Err(ZsanError::UnsupportedVersion(version));
+        }
+        let stored = first_byte & STORED_FLAG != 0;
+        if stored && first_byte & (ENTROPY_FLAG | ENCODE_MODE_MASK) != 0 {
+            return Err(ZsanError::InvalidHeader(first_byte));
+        }
+
+        let mut features = 0_u64;
+        let mut len = 1;
+        if first_byte & EXTENSION_FLAG != 0 {
+            loop {
+                let Some(&b) = input.get(len) else {
+                    return Err(ZsanError::Truncated);
+                };
+                let shift = 7 * (len - 1);
+                if shift >= 64 {
+                    return Err(ZsanError::InvalidHeader(first_byte));
+                }
+                features |= ((b & EXTENSION_FEATURE_MASK) as u64) << shift;
+                len += 1;
+                if b & EXTENSION_MORE_FLAG == 0 {
+                    break;
+                }
+            }
+        }
+        if let Some(bit) = (0..64).find(|bit| (features & !KNOWN_FEATURES) & (1 << bit) != 0) {
+            return Err(ZsanError::UnsupportedFeature(bit));
+        }
+        if features & ORDERED_FEATURE != 0
+            && first_byte & (STORED_FLAG | ENTROPY_FLAG | ENCODE_MODE_MASK) != 0
+        {
+            return Err(ZsanError::InvalidHeader(first_byte));
+        }
+
+        Ok(Header {
+            first_byte,
+            stored,
+            entropy: first_byte & ENTROPY_FLAG != 0,
+            features,
+            len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let header = Header::parse(&[0b_0100_0011, b'A']).unwrap();
+        assert_eq!(header.first_byte & ENCODE_MODE_MASK, FIRST_BYTE_DECIMAL);
+        assert!(!header.stored && !header.entropy);
+        assert_eq!(header.len, 1);
+
+        // 空的扩展字节
+        let header = Header::parse(&[0b_1100_0100, 0b_1000_0000, 0, b'A']).unwrap();
+        assert!(header.entropy);
+        assert_eq!(header.len, 3);
+
+        let header = Header::parse(&[0b_1100_0000, 0b_0000_0001, b'A']).unwrap();
+        assert_eq!(header.features, CHECKSUM_FEATURE);
+        assert_eq!(header.len, 2);
+
+        let header = Header::parse(&[0b_1100_0000, 0b_0000_0010, b'A']).unwrap();
+        assert_eq!(header.features, ORDERED_FEATURE);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Header::parse(&[]), Err(ZsanError::Truncated));
+        assert_eq!(Header::parse(b"1"), Err(ZsanError::InvalidHeader(b'1')));
+        assert_eq!(
+            Header::parse(&[0b_0101_0000]),
+            Err(ZsanError::UnsupportedVersion(1))
+        );
+        assert_eq!(
+            Header::parse(&[0b_0100_1001]),
+            Err(ZsanError::InvalidHeader(0b_0100_1001))
+        );
+        assert_eq!(
+            Header::parse(&[0b_1100_0000, 0b_1000_0000]),
+            Err(ZsanError::Truncated)
+        );
+        assert_eq!(
+            Header::parse(&[0b_1100_0000, 0b_1000_0000, 0b_0000_0100]),
+            Err(ZsanError::UnsupportedFeature(9))
+        );
+        assert_eq!(
+            Header::parse(&[0b_1100_1000, 0b_0000_0010]),
+            Err(ZsanError::InvalidHeader(0b_1100_1000))
+        );
+    }
+}
@@ -1,6 +1,62 @@
 mod all_ascii;
+mod checksum;
+#[cfg(feature = "tokio")]
+mod codec;
+mod compressor;
+mod dump;
+mod entropy;
+mod error;
+mod frame;
+mod header;
+mod log;
+mod matcher;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod numbers;
+mod optimal;
+mod ordered;
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod stream;
+mod tokens;
+mod transcode;
 mod vle_variants;
 mod zsan_parser;
 
 mod zsan;
+#[cfg(feature = "tokio")]
+pub use codec::ZsanCodec;
+pub use compressor::*;
+pub use dump::{ZsanDump, dump};
+pub use error::*;
+pub use frame::decompress_frames;
+pub use header::FORMAT_VERSION;
+pub use log::{ZsanLogReader, ZsanLogWriter};
+pub use matcher::CompressedMatcher;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapArchive;
+pub use numbers::{Numbers, ZsanNumber, nth_number, numbers};
+#[cfg(feature = "parallel")]
+pub use parallel::{compress_parallel, decompress_parallel};
+#[cfg(feature = "serde")]
+pub use serde::ZsanString;
+pub use stream::Decoder;
+pub use tokens::{Token, Tokens};
 pub use zsan::*;
+
+/// 只给 `benches/parser.rs` 使用, 不属于公开API
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench {
+    /// 解析出的块数
+    pub fn parse(src: &[u8]) -> usize {
+        crate::zsan_parser::retrave_blocks(src).2.len()
+    }
+
+    /// 逐字节解析出的块数
+    pub fn parse_scalar(src: &[u8]) -> usize {
+        crate::zsan_parser::retrave_blocks_scalar(src).2.len()
+    }
+}
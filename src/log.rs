@@ -0,0 +1,619 @@
+//! Append-only log files of compressed records.
+//!
+//! A log is a sequence of entries:
+//!
+//! - a record: a frame as written by [`Compressor::compress_frames`], the length of
+//!   the compressed record as a varint followed by the record, always with the
+//!   checksum feature;
+//! - a sync marker: the 9 bytes `SYNC_MARKER`, a frame whose payload starts with a
+//!   byte no record starts with. Every log starts with one, and the writer adds one
+//!   every [`ZsanLogWriter::sync_interval`] records and on [`ZsanLogWriter::sync`].
+//!
+//! A crash can leave the last record half written, its length running past the end
+//! of the file. [`ZsanLogReader`] drops it, and [`ZsanLogWriter::open`] truncates
+//! it before appending. Other damaged bytes are skipped up to the next sync marker
+//! or intact record, and are kept by the writer. A record is at most
+//! 16 MiB compressed unless both sides set another limit, so a damaged length never
+//! makes the reader buffer more than that.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::frame::{compress_frame, next_frame};
+use crate::header::*;
+use crate::vle_variants::decode_0;
+use crate::{Compressor, ZsanError};
+
+/// 同步标记: 长度为8的帧, 内容以不合法的首字节开头, 不会和记录混淆
+const SYNC_MARKER: [u8; 9] = [0x08, 0x00, 0xff, b'Z', b'S', b'A', b'N', 0xff, 0x0a];
+
+/// 空行压缩后没有字节, 写成带校验和的空的原样存储记录, 长度为0的帧一定是损坏的数据
+const EMPTY_RECORD: [u8; 7] = [
+    0x06,
+    EXTENSION_FLAG | ZSAN_FLAG_MASK | STORED_FLAG,
+    CHECKSUM_FEATURE as u8,
+    0,
+    0,
+    0,
+    0,
+];
+
+/// 每次从文件读取的字节数
+const READ_SIZE: usize = 64 * 1024;
+
+/// 64位长度的varint最多10个字节
+const MAX_LEN_BYTES: usize = 10;
+
+/// 带校验和的记录的头部: 首字节和最多10个扩展字节
+const MAX_HEADER_BYTES: usize = 11;
+
+/// 记录压缩后的默认长度上限, 读写两边相同
+const DEFAULT_MAX_RECORD_LEN: usize = 16 << 20;
+
+/// Appends compressed lines to a log file that [`ZsanLogReader`] reads back, also
+/// after a crash.
+///
+/// Every line is one length-prefixed record with a checksum. Sync markers between
+/// the records let the reader find the next intact record after a damaged one. A
+/// record torn by a crash is detected by its length and truncated the next time the
+/// log is opened for writing.
+///
+/// ```
+/// # let dir = tempfile::tempdir()?;
+/// # let path = dir.path().join("records.zlog");
+/// let mut writer = zsan::ZsanLogWriter::open(&path)?;
+/// writer.append("6224      ABC20200902       1312")?;
+/// writer.append("9951      EFG0990     132230")?;
+/// writer.sync()?;
+///
+/// let records = zsan::ZsanLogReader::open(&path)?.collect::<std::io::Result<Vec<_>>>()?;
+/// assert_eq!(records, ["6224      ABC20200902       1312", "9951      EFG0990     132230"]);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// Records are buffered and reach the file on [`ZsanLogWriter::sync`], when the
+/// buffer is full, or when the writer is dropped. Only `sync` waits until they are
+/// on disk.
+#[derive(Debug)]
+pub struct ZsanLogWriter {
+    file: BufWriter<File>,
+    compressor: Compressor,
+    sync_interval: usize,
+    max_record_len: usize,
+    /// 上一个同步标记之后的记录数
+    unmarked: usize,
+    frame: Vec<u8>,
+}
+
+impl ZsanLogWriter {
+    /// Open the log at `path` for appending with the default [`Compressor`],
+    /// creating it if it does not exist.
+    ///
+    /// The log is read once to find its end. A record torn by a crash at the end is
+    /// truncated first, see [`ZsanLogReader::valid_len`]. Other damaged bytes at
+    /// the end are kept, and a sync marker separates them from the new records.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_compressor(path, Compressor::default())
+    }
+
+    /// Same as [`ZsanLogWriter::open`], compressing with `compressor`. The checksum is
+    /// always on, and order-preserving encoding is not used.
+    pub fn with_compressor(path: impl AsRef<Path>, mut compressor: Compressor) -> io::Result<Self> {
+        compressor.checksum = true;
+        compressor.order_preserving = false;
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut reader = ZsanLogReader::new(&file);
+        let head = reader.fill(0, SYNC_MARKER.len())?;
+        // 崩溃时可能只写了开头的同步标记的一部分
+        if !head.starts_with(&SYNC_MARKER) && !SYNC_MARKER.starts_with(head) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a zsan log: missing sync marker",
+            ));
+        }
+        for record in reader.by_ref() {
+            record?;
+        }
+        let (valid_len, end, torn) = (reader.valid_len, reader.position, reader.torn);
+
+        // 只截掉崩溃时没写完的最后一条, 其余损坏的数据保留, 之后先写同步标记
+        let mark = if torn {
+            file.set_len(valid_len)?;
+            valid_len == 0
+        } else {
+            valid_len == 0 || valid_len < end
+        };
+        file.seek(SeekFrom::Start(if torn { valid_len } else { end }))?;
+
+        let mut writer = ZsanLogWriter {
+            file: BufWriter::new(file),
+            compressor,
+            sync_interval: 128,
+            max_record_len: DEFAULT_MAX_RECORD_LEN,
+            unmarked: 0,
+            frame: Vec::new(),
+        };
+        if mark {
+            writer.file.write_all(&SYNC_MARKER)?;
+        }
+        Ok(writer)
+    }
+
+    /// Write a sync marker after every `records` records, 128 by default. A damaged
+    /// record loses at most the records up to the next marker.
+    pub fn sync_interval(mut self, records: usize) -> Self {
+        self.sync_interval = records.max(1);
+        self
+    }
+
+    /// Refuse to append records longer than `bytes` compressed, 16 MiB by default. Readers of the log need at least the
+    /// same limit, see [`ZsanLogReader::max_record_len`].
+    pub fn max_record_len(mut self, bytes: usize) -> Self {
+        self.max_record_len = bytes;
+        self
+    }
+
+    /// Compress `line` and append it as one record.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] and appends nothing if the record
+    /// is longer than [`ZsanLogWriter::max_record_len`].
+    pub fn append(&mut self, line: &str) -> io::Result<()> {
+        self.frame.clear();
+        if line.is_empty() {
+            self.frame.extend_from_slice(&EMPTY_RECORD);
+        } else {
+            compress_frame(&self.compressor, line, &mut self.frame);
+        }
+        if decode_0(&self.frame).is_some_and(|(len, _)| len > self.max_record_len as u64) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "record longer than max_record_len",
+            ));
+        }
+        self.file.write_all(&self.frame)?;
+        self.unmarked += 1;
+        if self.unmarked >= self.sync_interval {
+            self.file.write_all(&SYNC_MARKER)?;
+            self.unmarked = 0;
+        }
+        Ok(())
+    }
+
+    /// Write a sync marker and wait until everything appended so far is on disk.
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.unmarked > 0 {
+            self.file.write_all(&SYNC_MARKER)?;
+            self.unmarked = 0;
+        }
+        self.file.flush()?;
+        self.file.get_ref().sync_data()
+    }
+}
+
+/// Reads the records of a log written by [`ZsanLogWriter`].
+///
+/// Yields the text of every intact record in order, reading the log in chunks. A
+/// record that does not decode, e.g. because a crash interrupted writing it, is
+/// skipped together with everything up to the next sync marker or intact record.
+/// Records are recognized by their checksum. A length above
+/// [`ZsanLogReader::max_record_len`] is damaged, so only that many bytes are ever
+/// buffered for a record. An I/O error is yielded once and ends the iteration.
+#[derive(Debug)]
+pub struct ZsanLogReader<R = File> {
+    reader: R,
+    /// 读进来还没有处理完的字节, 当前位置是 `buffer[start]`
+    buffer: Vec<u8>,
+    start: usize,
+    eof: bool,
+    failed: bool,
+    max_record_len: usize,
+    /// 当前位置在日志中的偏移
+    position: u64,
+    valid_len: u64,
+    skipped: u64,
+    /// 最后一段损坏的数据以长度超出末尾的记录开头, 是崩溃时没写完的记录
+    torn: bool,
+}
+
+/// 当前位置的一项
+enum Entry {
+    Marker,
+    /// 原文和帧的长度
+    Record(String, usize),
+    /// 长度超出了日志的末尾
+    Torn,
+    Damaged,
+    End,
+}
+
+impl ZsanLogReader {
+    /// Open the log at `path` for reading.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> ZsanLogReader<R> {
+    /// A reader over the bytes of a log, e.g. a `&[u8]`.
+    pub fn new(reader: R) -> Self {
+        ZsanLogReader {
+            reader,
+            buffer: Vec::new(),
+            start: 0,
+            eof: false,
+            failed: false,
+            max_record_len: DEFAULT_MAX_RECORD_LEN,
+            position: 0,
+            valid_len: 0,
+            skipped: 0,
+            torn: false,
+        }
+    }
+
+    /// Treat records longer than `bytes` compressed as damaged, 16 MiB by default.
+    /// Use the limit the log was written with.
+    pub fn max_record_len(mut self, bytes: usize) -> Self {
+        self.max_record_len = bytes;
+        self
+    }
+
+    /// End of the last intact entry read so far. Once the reader is exhausted, this
+    /// is the length of the log without damaged bytes at its end.
+    pub fn valid_len(&self) -> u64 {
+        self.valid_len
+    }
+
+    /// Number of damaged bytes skipped so far.
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped
+    }
+
+    /// 当前位置之后从 `at` 开始的字节, 日志没有结束时至少有 `len` 个
+    fn fill(&mut self, at: usize, len: usize) -> io::Result<&[u8]> {
+        let needed = at.saturating_add(len);
+        while self.buffer.len() - self.start < needed && !self.eof {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+            let filled = self.buffer.len();
+            self.buffer.resize(filled + READ_SIZE, 0);
+            let read = loop {
+                match self.reader.read(&mut self.buffer[filled..]) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    read => break read,
+                }
+            };
+            self.buffer.truncate(filled + *read.as_ref().unwrap_or(&0));
+            self.eof = read? == 0;
+        }
+        let available = &self.buffer[self.start..];
+        Ok(&available[at.min(available.len())..])
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.start += len;
+        self.position += len as u64;
+    }
+
+    /// 当前位置之后从 `at` 开始的一项
+    fn entry(&mut self, at: usize) -> io::Result<Entry> {
+        let head = self.fill(at, SYNC_MARKER.len().max(MAX_LEN_BYTES))?;
+        if head.is_empty() {
+            return Ok(Entry::End);
+        }
+        if head.starts_with(&SYNC_MARKER) {
+            return Ok(Entry::Marker);
+        }
+        let Some((len, len_bytes)) = decode_0(head) else {
+            // 长度本身不完整
+            let torn = head.len() < MAX_LEN_BYTES;
+            return Ok(if torn { Entry::Torn } else { Entry::Damaged });
+        };
+        // 损坏的长度可能很大, 超过上限的不读进来
+        let Some(frame_len) = usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= self.max_record_len)
+            .and_then(|len| len.checked_add(len_bytes))
+        else {
+            return Ok(Entry::Damaged);
+        };
+        // 先检查头部, 不是记录时不用读进整个长度
+        let head = self.fill(at, len_bytes + MAX_HEADER_BYTES)?;
+        let payload = &head[len_bytes..head.len().min(frame_len)];
+        match Header::parse(payload) {
+            Ok(header) if header.features & CHECKSUM_FEATURE != 0 => {}
+            Err(ZsanError::Truncated) if head.len() < frame_len => return Ok(Entry::Torn),
+            _ => return Ok(Entry::Damaged),
+        }
+        let head = self.fill(at, frame_len)?;
+        Ok(match next_frame(head) {
+            Ok((payload, len)) => match Self::decode(payload) {
+                Some(text) => Entry::Record(text, len),
+                None => Entry::Damaged,
+            },
+            Err(_) => Entry::Torn,
+        })
+    }
+
+    /// 完整的记录的原文, 损坏时返回 `None`
+    fn decode(payload: &[u8]) -> Option<String> {
+        let mut text = Vec::new();
+        crate::decompress(payload, &mut text).ok()?;
+        String::from_utf8(text).ok()
+    }
+
+    /// 跳到下一个同步标记或者完整的记录, 没有时跳到末尾
+    fn skip_damaged(&mut self, first: Entry) -> io::Result<()> {
+        let mut at = 1;
+        loop {
+            match self.entry(at)? {
+                Entry::Marker | Entry::Record(..) => break,
+                Entry::End => {
+                    self.torn = matches!(first, Entry::Torn);
+                    break;
+                }
+                Entry::Torn | Entry::Damaged => at += 1,
+            }
+        }
+        self.skipped += at as u64;
+        self.consume(at);
+        Ok(())
+    }
+
+    fn try_next(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.entry(0)? {
+                Entry::End => return Ok(None),
+                Entry::Marker => {
+                    self.consume(SYNC_MARKER.len());
+                    self.valid_len = self.position;
+                }
+                Entry::Record(text, len) => {
+                    self.consume(len);
+                    self.valid_len = self.position;
+                    return Ok(Some(text));
+                }
+                damaged @ (Entry::Torn | Entry::Damaged) => self.skip_damaged(damaged)?,
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for ZsanLogReader<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        if self.failed {
+            return None;
+        }
+        let result = self.try_next();
+        self.failed = result.is_err();
+        result.transpose()
+    }
+}
+
+impl<R: Read> std::iter::FusedIterator for ZsanLogReader<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{READ_SIZE, SYNC_MARKER, ZsanLogReader, ZsanLogWriter};
+    use std::path::Path;
+
+    fn lines(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| match i % 5 {
+                0 => String::new(),
+                _ => format!("{i:>8} ABC{:08}      {}.{:02}", i * 7919, i % 1000, i % 100),
+            })
+            .collect()
+    }
+
+    fn write_log(path: &Path, lines: &[String], sync_interval: usize) {
+        let mut writer = ZsanLogWriter::open(path)
+            .unwrap()
+            .sync_interval(sync_interval);
+        for line in lines {
+            writer.append(line).unwrap();
+        }
+        writer.sync().unwrap();
+    }
+
+    fn read_log(path: &Path) -> Vec<String> {
+        ZsanLogReader::open(path)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn test_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.zlog");
+        let lines = lines(50);
+        write_log(&path, &lines[..20], 8);
+        // 重新打开后接着追加
+        write_log(&path, &lines[20..], 8);
+        assert_eq!(read_log(&path), lines);
+
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.starts_with(&SYNC_MARKER));
+        assert!(data.ends_with(&SYNC_MARKER));
+        let mut reader = ZsanLogReader::new(&data[..]);
+        assert_eq!(reader.by_ref().count(), 50);
+        assert_eq!(reader.valid_len(), data.len() as u64);
+        assert_eq!(reader.skipped_bytes(), 0);
+
+        // 空文件
+        let empty = dir.path().join("empty.zlog");
+        write_log(&empty, &[], 8);
+        assert_eq!(std::fs::read(&empty).unwrap(), SYNC_MARKER);
+        assert!(read_log(&empty).is_empty());
+    }
+
+    #[test]
+    fn test_torn_last_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.zlog");
+        let lines = lines(10);
+        write_log(&path, &lines, 4);
+        let full = std::fs::read(&path).unwrap();
+        let synced = full.len() - SYNC_MARKER.len();
+
+        // 在最后一条记录写到一半时崩溃, 包括只写了长度的情况
+        let mut reader = ZsanLogReader::new(&full[..synced]);
+        reader.by_ref().take(9).for_each(drop);
+        let last_start = reader.valid_len() as usize;
+        for cut in last_start + 1..synced {
+            std::fs::write(&path, &full[..cut]).unwrap();
+            let mut reader = ZsanLogReader::open(&path).unwrap();
+            let read: Vec<String> = reader.by_ref().map(Result::unwrap).collect();
+            assert_eq!(read, lines[..9]);
+            assert_eq!(reader.valid_len(), last_start as u64);
+            assert_eq!(reader.skipped_bytes(), (cut - last_start) as u64);
+
+            // 打开写入时截掉不完整的记录
+            write_log(&path, &lines[9..], 4);
+            assert_eq!(read_log(&path), lines);
+        }
+    }
+
+    #[test]
+    fn test_damaged_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.zlog");
+        let lines = lines(12);
+        write_log(&path, &lines, 4);
+        let mut data = std::fs::read(&path).unwrap();
+
+        // 第6条记录的内容损坏, 只丢掉这一条
+        let mut reader = ZsanLogReader::new(&data[..]);
+        reader.by_ref().take(5).for_each(drop);
+        let damaged = reader.valid_len() as usize;
+        data[damaged + 3] ^= 0x10;
+        let mut reader = ZsanLogReader::new(&data[..]);
+        let read: Vec<String> = reader.by_ref().map(Result::unwrap).collect();
+        assert_eq!(read, [&lines[..5], &lines[6..]].concat());
+        assert_eq!(reader.valid_len(), data.len() as u64);
+        let skipped = reader.skipped_bytes();
+        assert!(skipped > 0);
+
+        // 全是0的尾部, 比如崩溃后文件系统补上的块
+        data.extend_from_slice(&[0; 64]);
+        let mut reader = ZsanLogReader::new(&data[..]);
+        assert_eq!(reader.by_ref().count(), 11);
+        assert_eq!(reader.valid_len(), data.len() as u64 - 64);
+        assert_eq!(reader.skipped_bytes(), skipped + 64);
+
+        // 不是没写完的记录, 打开写入时保留, 在后面追加
+        std::fs::write(&path, &data).unwrap();
+        let more = self::lines(15);
+        write_log(&path, &more[12..], 4);
+        assert!(std::fs::read(&path).unwrap().starts_with(&data));
+        let read = read_log(&path);
+        assert_eq!(read, [&lines[..5], &lines[6..], &more[12..]].concat());
+    }
+
+    #[test]
+    fn test_damaged_last_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.zlog");
+        let lines = lines(20);
+        write_log(&path, &lines[..10], 128);
+        let clean = std::fs::read(&path).unwrap();
+
+        // 最后一段中间的记录损坏, 包括长度损坏, 重新打开写入后之后的记录都还在
+        let mut reader = ZsanLogReader::new(&clean[..]);
+        reader.by_ref().take(3).for_each(drop);
+        let start = reader.valid_len() as usize;
+        reader.next();
+        let end = reader.valid_len() as usize;
+        for offset in start..end {
+            for bit in [0x01, 0x40, 0x80] {
+                let mut data = clean.clone();
+                data[offset] ^= bit;
+                std::fs::write(&path, &data).unwrap();
+                write_log(&path, &lines[10..], 128);
+                assert!(std::fs::read(&path).unwrap().starts_with(&data));
+
+                let mut read = read_log(&path).into_iter();
+                assert!(read.by_ref().take(3).eq(lines[..3].iter().cloned()));
+                let rest: Vec<String> = read.collect();
+                // 损坏的记录偶尔还能解出同样的内容, 比如改的是长度的无效位
+                assert!(
+                    rest == lines[4..] || rest == lines[3..],
+                    "offset {offset} bit {bit}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_damaged_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.zlog");
+        // 日志比读取的块大得多
+        let lines: Vec<String> = (0..4000)
+            .map(|i| format!("{i:>8} {}", "ABCDEFGH".repeat(40)))
+            .collect();
+        write_log(&path, &lines, 128);
+        let mut data = std::fs::read(&path).unwrap();
+        assert!(data.len() > 16 * READ_SIZE);
+
+        // 长度改成32 MiB, 之后是带校验和的首字节
+        let mut reader = ZsanLogReader::new(&data[..]);
+        reader.by_ref().take(5).for_each(drop);
+        let damaged = reader.valid_len() as usize;
+        data[damaged..damaged + 6].copy_from_slice(&[0xff, 0xff, 0xff, 0x0f, 0xc1, 0x01]);
+        let expected = [&lines[..5], &lines[6..]].concat();
+        let read: Vec<String> = ZsanLogReader::new(&data[..]).map(Result::unwrap).collect();
+        assert_eq!(read, expected);
+
+        // 跳过损坏的数据时读到的长度也不超过上限, 不会把整个日志读进来
+        let mut reader = ZsanLogReader::new(&data[..]).max_record_len(4096);
+        let read: Vec<String> = reader.by_ref().take(6).map(Result::unwrap).collect();
+        assert_eq!(read, expected[..6]);
+        assert!(reader.buffer.capacity() <= 4 * READ_SIZE);
+        assert_eq!(reader.count(), expected.len() - 6);
+
+        // 损坏的长度不是没写完的记录, 打开写入时保留.
+        // 写入时拒绝超过上限的记录, 不写任何东西
+        std::fs::write(&path, &data).unwrap();
+        let mut writer = ZsanLogWriter::open(&path).unwrap().max_record_len(64);
+        writer.append(&lines[0][..40]).unwrap();
+        let error = writer.append(&lines[0]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        writer.sync().unwrap();
+        assert!(std::fs::read(&path).unwrap().starts_with(&data));
+        assert_eq!(
+            read_log(&path),
+            [&expected[..], &[lines[0][..40].to_owned()]].concat()
+        );
+    }
+
+    #[test]
+    fn test_not_a_log() {
+        let mut reader = ZsanLogReader::new(&b"plain text\n"[..]);
+        assert!(reader.next().is_none());
+        assert_eq!(reader.valid_len(), 0);
+        assert_eq!(reader.skipped_bytes(), 11);
+
+        // 不是日志的文件不会被截断
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "plain text\n").unwrap();
+        let error = ZsanLogWriter::open(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::read(&path).unwrap(), b"plain text\n");
+
+        // 只写了一部分的同步标记
+        std::fs::write(&path, &SYNC_MARKER[..4]).unwrap();
+        write_log(&path, &lines(3), 8);
+        assert_eq!(read_log(&path), lines(3));
+    }
+}
@@ -0,0 +1,389 @@
+//! Substring search on compressed records.
+
+use std::collections::VecDeque;
+
+use crate::zsan_parser::{Block, is_number_byte, retrave_blocks};
+use crate::{Token, Tokens, ZsanError};
+
+/// Searches the text of compressed records for a fixed pattern without decompressing
+/// them.
+///
+/// ```
+/// let mut compressed = Vec::new();
+/// zsan::compress("6224      ABC20200902       -1312.25", &mut compressed);
+///
+/// assert!(zsan::CompressedMatcher::new("ABC").is_match(&compressed).unwrap());
+/// assert!(zsan::CompressedMatcher::new("  -1312.2").is_match(&compressed).unwrap());
+/// assert!(!zsan::CompressedMatcher::new("1312.3").is_match(&compressed).unwrap());
+/// ```
+///
+/// The result is the same as searching the decompressed text. The pattern is split
+/// into numbers, runs of spaces and literal bytes like the compressor splits text.
+/// Literal bytes are compared as is, and numbers and runs of spaces inside the
+/// pattern with the tokens of the record. Only a number token the pattern starts or
+/// ends in, or one that covers only part of a number as an optimal parse may write,
+/// is decoded. A pattern without digits, spaces, `-` and `.` can only occur
+/// in literal bytes, so only those are searched.
+#[derive(Debug, Clone)]
+pub struct CompressedMatcher {
+    pattern: String,
+    /// 模式里没有数字和空格token能产生的字节
+    literal_only: bool,
+    /// 从每个位置开始的空格数
+    spaces: Vec<usize>,
+    /// 两边都有不属于数字的字节的数字块, 按起点索引, 以及长度.
+    /// 原文的数字token与它相同时不需要解码
+    numbers: Vec<Option<(Token<'static>, usize)>>,
+    /// 字面量之后的token可能在模式的哪些位置开始
+    token_starts: Vec<usize>,
+}
+
+impl CompressedMatcher {
+    pub fn new(pattern: &str) -> Self {
+        let bytes = pattern.as_bytes();
+        let len = bytes.len();
+        let literal_only = !bytes.iter().any(|&b| b == b' ' || is_number_byte(b));
+
+        let mut spaces = vec![0; len + 1];
+        for i in (0..len).rev() {
+            if bytes[i] == b' ' {
+                spaces[i] = spaces[i + 1] + 1;
+            }
+        }
+
+        // 解析器不会回看, 遇到不属于数字的字节 (连同后面的空格) 之后两边从同一个位置重新开始
+        let start = bytes
+            .iter()
+            .position(|&b| !is_number_byte(b))
+            .map_or(len, |i| i + spaces[i].max(1));
+        let end = bytes
+            .iter()
+            .rposition(|&b| !is_number_byte(b))
+            .map_or(0, |i| i + 1);
+        // 原文中这个范围的数字与模式的数字块相同: 之前有不属于数字的字节, 解析从同一个位置开始;
+        // 之后的数字可能在原文里继续, 需要解码比较
+        let exact = start..end.max(start);
+
+        let mut numbers = vec![None; len];
+        for block in retrave_blocks(bytes).2 {
+            if let Block::Numerical(start, size, number) = block
+                && exact.start <= start
+                && start + size <= exact.end
+            {
+                let token = Token::Number {
+                    value: number.base,
+                    negative: number.negative,
+                    decimal_places: number.decimal_places as u8,
+                };
+                numbers[start] = Some((token, size));
+            }
+        }
+
+        // token的文本以数字, `-`, `.` 或空格开始. 最优划分的token可能从数字或空格串中间开始
+        let token_starts = (1..len)
+            .filter(|&i| !exact.contains(&i) || bytes[i] == b' ' || is_number_byte(bytes[i]))
+            .collect();
+
+        CompressedMatcher {
+            pattern: pattern.to_owned(),
+            literal_only,
+            spaces,
+            numbers,
+            token_starts,
+        }
+    }
+
+    /// Whether the text of the compressed record `input` contains the pattern.
+    ///
+    /// Fails if `input` is malformed before the first match. The checksum is not
+    /// verified.
+    pub fn is_match(&self, input: &[u8]) -> Result<bool, ZsanError> {
+        let tokens = Tokens::new(input)?;
+        if self.pattern.is_empty() {
+            return Ok(true);
+        }
+
+        if self.literal_only {
+            for token in tokens {
+                if let (_, Token::Literal(bytes)) = token?
+                    && contains(&bytes, &self.pattern)
+                {
+                    return Ok(true);
+                }
+            }
+            return Ok(false);
+        }
+
+        let mut rest = Lookahead {
+            tokens,
+            buffer: VecDeque::new(),
+        };
+        let (mut first, mut scratch) = (Vec::new(), Vec::new());
+        while let Some(token) = rest.next() {
+            if self.starts_in(&token?, &mut rest, &mut first, &mut scratch) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// 是否有从 `token` 里开始的匹配
+    fn starts_in(
+        &self,
+        token: &Token,
+        rest: &mut Lookahead,
+        first: &mut Vec<u8>,
+        scratch: &mut Vec<u8>,
+    ) -> bool {
+        let pattern = self.pattern.as_bytes();
+        match token {
+            Token::Literal(bytes) => {
+                contains(bytes, &self.pattern)
+                    || self
+                        .token_starts
+                        .iter()
+                        .take_while(|&&i| i <= bytes.len())
+                        .any(|&i| {
+                            bytes.ends_with(&pattern[..i]) && self.matches_from(rest, i, scratch)
+                        })
+            }
+            // 原文的空格串是一个token, 模式开头的空格只能是它的结尾
+            &Token::Spaces(count) => {
+                let len = self.spaces[0].min(count);
+                len > 0 && (len == pattern.len() || self.matches_from(rest, len, scratch))
+            }
+            Token::Number { .. } => {
+                if !is_number_byte(pattern[0]) {
+                    return false;
+                }
+                first.clear();
+                token.write(first);
+                (0..first.len()).any(|start| {
+                    let text = &first[start..];
+                    if text.len() >= pattern.len() {
+                        text.starts_with(pattern)
+                    } else {
+                        pattern.starts_with(text) && self.matches_from(rest, text.len(), scratch)
+                    }
+                })
+            }
+        }
+    }
+
+    /// 模式从 `i` 开始的部分是否与之后的token的文本相同
+    fn matches_from(&self, rest: &mut Lookahead, mut i: usize, scratch: &mut Vec<u8>) -> bool {
+        let pattern = self.pattern.as_bytes();
+        let mut index = 0;
+        while i < pattern.len() {
+            let Some(token) = rest.peek(index) else {
+                return false;
+            };
+            let remaining = &pattern[i..];
+            i += match token {
+                Token::Literal(bytes) => {
+                    let len = bytes.len().min(remaining.len());
+                    if bytes[..len] != remaining[..len] {
+                        return false;
+                    }
+                    len
+                }
+                &Token::Spaces(count) => {
+                    let len = count.min(remaining.len());
+                    if self.spaces[i] < len {
+                        return false;
+                    }
+                    len
+                }
+                Token::Number { .. } => match &self.numbers[i] {
+                    // 中间的数字token与模式的数字块相同时不需要解码.
+                    // 不同时仍可能是最优划分拆开的数字的一部分
+                    Some((number, len)) if number == token => *len,
+                    _ => {
+                        scratch.clear();
+                        token.write(scratch);
+                        let len = scratch.len().min(remaining.len());
+                        if scratch[..len] != remaining[..len] {
+                            return false;
+                        }
+                        len
+                    }
+                },
+            };
+            index += 1;
+        }
+        true
+    }
+}
+
+/// 跨过token边界的匹配需要看后面的token
+struct Lookahead<'a> {
+    tokens: Tokens<'a>,
+    buffer: VecDeque<Result<Token<'a>, ZsanError>>,
+}
+
+impl<'a> Lookahead<'a> {
+    fn next(&mut self) -> Option<Result<Token<'a>, ZsanError>> {
+        self.buffer
+            .pop_front()
+            .or_else(|| Some(self.tokens.next()?.map(|(_, token)| token)))
+    }
+
+    /// 当前token之后的第 `index` 个token. 格式错误的token在轮到它时由 `next` 返回
+    fn peek(&mut self, index: usize) -> Option<&Token<'a>> {
+        while self.buffer.len() <= index {
+            let token = self.tokens.next()?.map(|(_, token)| token);
+            self.buffer.push_back(token);
+        }
+        self.buffer[index].as_ref().ok()
+    }
+}
+
+/// token流里的字面量都是ASCII, 可以用 `str` 的子串查找
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    match std::str::from_utf8(haystack) {
+        Ok(haystack) => haystack.contains(needle),
+        // 原样存储的任意字节
+        Err(_) => haystack
+            .windows(needle.len())
+            .any(|window| window == needle.as_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompressedMatcher;
+    use crate::{Compressor, ZsanError};
+    use proptest::prelude::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        let mut compressed = Vec::new();
+        crate::compress(text, &mut compressed);
+        CompressedMatcher::new(pattern)
+            .is_match(&compressed)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pattern_tokens() {
+        let matcher = CompressedMatcher::new("12 A-5 3.25 B7");
+        // 模式两端和字母处不会有token开始
+        assert_eq!(matcher.token_starts, [1, 2, 4, 5, 6, 7, 8, 9, 10, 11, 13]);
+        let numbers: Vec<_> = (0..14)
+            .filter_map(|i| Some((i, matcher.numbers[i].as_ref()?.1)))
+            .collect();
+        assert_eq!(numbers, [(4, 2), (7, 4)]);
+        // 模式没有不属于数字的字节时两端重合, 所有数字都要解码
+        assert!(
+            CompressedMatcher::new("1312.25")
+                .numbers
+                .iter()
+                .all(Option::is_none)
+        );
+    }
+
+    #[test]
+    fn test_is_match() {
+        let text = "6224      ABC20200902       -1312.25 0.1234567890123456 1.";
+        for pattern in [
+            "", "ABC", "C2020", "6224 ", "     -13", "-1312.25", "12.25 0.", "56 1.", " 1.",
+        ] {
+            assert!(is_match(pattern, text), "{pattern:?}");
+        }
+        for pattern in [
+            "ABD",
+            "  -1312.3",
+            "-1312.250",
+            "62240",
+            "1.2.",
+            "        -",
+        ] {
+            assert!(!is_match(pattern, text), "{pattern:?}");
+        }
+        assert!(is_match("", ""));
+        assert!(!is_match("A", ""));
+
+        // 模式两端的数字在原文里可以更长, 或者被解析成别的数字
+        for (pattern, text) in [
+            ("A-5", "A-50"),
+            ("0.", "x 0.5"),
+            ("23.5 A", "1.23.5 A"),
+            ("12 34", "912 345"),
+            ("9 A", "99999999999999999999 A"),
+            (" 12 ", "A 12 B"),
+            ("A 12.5 -7 B", "x A 12.5 -7 B"),
+            ("A -  ", "A -   "),
+        ] {
+            assert!(is_match(pattern, text), "{pattern:?} in {text:?}");
+        }
+        // 中间的数字必须是原文里完整的数字
+        for (pattern, text) in [
+            ("A 12 B", "A 123 B"),
+            ("A 2 B", "A 12 B"),
+            ("A 12 B", "A -12 B"),
+            ("A  B", "A   B"),
+        ] {
+            assert!(!is_match(pattern, text), "{pattern:?} in {text:?}");
+        }
+
+        assert_eq!(
+            CompressedMatcher::new("B").is_match(&[0x40, b'A', 0x7f, b'B']),
+            Err(ZsanError::InvalidToken(1))
+        );
+        assert_eq!(
+            CompressedMatcher::new("A").is_match(&[0x40, b'A', 0x7f]),
+            Ok(true)
+        );
+        assert_eq!(
+            CompressedMatcher::new("").is_match(b"1"),
+            Err(ZsanError::InvalidHeader(b'1'))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn same_as_searching_the_text(
+            text in "[0-9 .\\-AB]{0,120}",
+            pattern in "[0-9 .\\-AB]{0,6}|[0-9 .\\-AB]{0,3}[ AB][0-9 .\\-]{1,6}[ AB][0-9 .\\-AB]{0,3}",
+            entropy in any::<bool>(),
+            optimal in any::<bool>(),
+            min_number_len in 0..4_usize,
+            min_space_run in 1..4_usize,
+            [spaces, negatives, decimals] in any::<[bool; 3]>(),
+        ) {
+            let mut compressed = Vec::new();
+            Compressor::builder()
+                .entropy(entropy)
+                .optimal_parse(optimal)
+                .min_number_len(min_number_len)
+                .min_space_run(min_space_run)
+                .spaces(spaces)
+                .negatives(negatives)
+                .decimals(decimals)
+                .build()
+                .compress(&text, &mut compressed);
+            let matcher = CompressedMatcher::new(&pattern);
+            prop_assert_eq!(matcher.is_match(&compressed), Ok(text.contains(&pattern)));
+        }
+
+        #[test]
+        fn finds_every_substring(
+            text in "[0-9 .\\-AB]{1,120}",
+            start in any::<prop::sample::Index>(),
+            len in 1..16_usize,
+            min_number_len in 0..4_usize,
+            [spaces, decimals, optimal] in any::<[bool; 3]>(),
+        ) {
+            let start = start.index(text.len());
+            let pattern = &text[start..text.len().min(start + len)];
+            let mut compressed = Vec::new();
+            Compressor::builder()
+                .min_number_len(min_number_len)
+                .spaces(spaces)
+                .decimals(decimals)
+                .optimal_parse(optimal)
+                .build()
+                .compress(&text, &mut compressed);
+            prop_assert_eq!(CompressedMatcher::new(pattern).is_match(&compressed), Ok(true));
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Memory-mapped files of length-prefixed records, enabled with the `mmap` feature.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::frame::next_frame;
+use crate::{Tokens, ZsanError};
+
+/// A file of frames, as written by [`Compressor::compress_frames`] or
+/// `ZsanCodec`, mapped into memory.
+///
+/// Records are borrowed from the mapping, so iterating, random access, [`Tokens`]
+/// and the other functions taking a compressed record work without copying.
+///
+/// ```
+/// # let dir = tempfile::tempdir()?;
+/// # let path = dir.path().join("records.zsan");
+/// let mut frames = Vec::new();
+/// zsan::Compressor::builder()
+///     .frame_size(1)
+///     .build()
+///     .compress_frames("6224      ABC20200902\n9951      EFG0990\n", &mut frames);
+/// std::fs::write(&path, &frames)?;
+///
+/// let archive = zsan::MmapArchive::open(&path)?;
+/// assert_eq!(archive.len(), 2);
+/// let mut text = Vec::new();
+/// for record in archive.iter() {
+///     text.clear();
+///     zsan::decompress(record, &mut text).unwrap();
+///     assert!(text.ends_with(b"\n"));
+/// }
+/// archive.decompress(1, &mut text).unwrap();
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// The frame lengths are read once by [`MmapArchive::open`], which keeps the start
+/// of every frame, 8 bytes per record. Records are decoded when they are used.
+///
+/// The file must not be changed while it is mapped, in particular not truncated:
+/// reading a mapped page that no longer exists kills the process.
+///
+/// [`Compressor::compress_frames`]: crate::Compressor::compress_frames
+#[derive(Debug)]
+pub struct MmapArchive {
+    /// 空文件不能映射
+    map: Option<Mmap>,
+    /// 每一帧在文件中的起点
+    frames: Vec<usize>,
+}
+
+impl MmapArchive {
+    /// Map the file at `path` and find its frames.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the file does not consist of
+    /// complete frames. The records themselves are not checked.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let map = if file.metadata()?.len() == 0 {
+            None
+        } else {
+            // 文件在映射期间不能被修改, 见上面的文档
+            Some(unsafe { Mmap::map(&file)? })
+        };
+        let frames = index_frames(map.as_deref().unwrap_or_default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(MmapArchive { map, frames })
+    }
+
+    /// The whole mapped file.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.map.as_deref().unwrap_or_default()
+    }
+
+    /// Number of records.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The compressed record `index`, borrowed from the file.
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        let start = *self.frames.get(index)?;
+        Some(self.frame(start))
+    }
+
+    /// The compressed records in order, borrowed from the file.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &[u8]> + DoubleEndedIterator {
+        self.frames.iter().map(|&start| self.frame(start))
+    }
+
+    /// Decompress the record `index` and append its text to `out`, see
+    /// [`crate::decompress`]. Reusing `out` avoids an allocation per record.
+    ///
+    /// Panics if `index` is not less than [`MmapArchive::len`].
+    pub fn decompress(&self, index: usize, out: &mut Vec<u8>) -> Result<(), ZsanError> {
+        crate::decompress(self.record(index), out)
+    }
+
+    /// The tokens of the record `index`, see [`Tokens`].
+    ///
+    /// Panics if `index` is not less than [`MmapArchive::len`].
+    pub fn tokens(&self, index: usize) -> Result<Tokens<'_>, ZsanError> {
+        Tokens::new(self.record(index))
+    }
+
+    fn record(&self, index: usize) -> &[u8] {
+        self.get(index).unwrap_or_else(|| {
+            panic!(
+                "record index {index} out of range for an archive of {} records",
+                self.len()
+            )
+        })
+    }
+
+    /// `open` 已经检查过每一帧
+    fn frame(&self, start: usize) -> &[u8] {
+        next_frame(&self.as_bytes()[start..]).unwrap().0
+    }
+}
+
+/// 找出每一帧的起点
+fn index_frames(data: &[u8]) -> Result<Vec<usize>, ZsanError> {
+    let mut frames = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let (_, len) = next_frame(&data[start..])?;
+        frames.push(start);
+        start += len;
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MmapArchive;
+    use crate::{Compressor, Token};
+
+    fn lines(count: usize) -> String {
+        (0..count)
+            .map(|i| {
+                format!(
+                    "{i:>8} ABC{:08}      {}.{:02}\n",
+                    i * 7919,
+                    i % 1000,
+                    i % 100
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.zsan");
+        let src = lines(500);
+        let mut frames = Vec::new();
+        Compressor::builder()
+            .frame_size(1)
+            .entropy(true)
+            .build()
+            .compress_frames(&src, &mut frames);
+        std::fs::write(&path, &frames).unwrap();
+
+        let archive = MmapArchive::open(&path).unwrap();
+        assert_eq!(archive.len(), 500);
+        assert_eq!(archive.as_bytes(), frames);
+        let mut text = Vec::new();
+        for record in archive.iter() {
+            crate::decompress(record, &mut text).unwrap();
+        }
+        assert_eq!(text, src.as_bytes());
+
+        // 随机访问, 记录借用映射的内存
+        let line = src.lines().nth(123).unwrap();
+        text.clear();
+        archive.decompress(123, &mut text).unwrap();
+        assert_eq!(text, format!("{line}\n").as_bytes());
+        let record = archive.get(123).unwrap();
+        assert!(archive.as_bytes().as_ptr_range().contains(&record.as_ptr()));
+        assert_eq!(archive.iter().rev().nth(500 - 1 - 123), Some(record));
+        assert!(archive.get(500).is_none());
+
+        let (_, first) = archive.tokens(123).unwrap().next().unwrap().unwrap();
+        assert_eq!(first, Token::Spaces(5));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.zsan");
+
+        std::fs::write(&path, b"").unwrap();
+        let archive = MmapArchive::open(&path).unwrap();
+        assert!(archive.is_empty());
+        assert_eq!(archive.iter().count(), 0);
+
+        // 最后一帧不完整
+        let mut frames = Vec::new();
+        Compressor::default().compress_frames("ABC   123\n", &mut frames);
+        std::fs::write(&path, &frames[..frames.len() - 1]).unwrap();
+        let error = MmapArchive::open(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        assert!(MmapArchive::open(dir.path().join("missing.zsan")).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "record index 1 out of range")]
+    fn test_out_of_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.zsan");
+        let mut frames = Vec::new();
+        Compressor::default().compress_frames("ABC   123\n", &mut frames);
+        std::fs::write(&path, &frames).unwrap();
+        let _ = MmapArchive::open(&path)
+            .unwrap()
+            .decompress(1, &mut Vec::new());
+    }
+}
@@ -0,0 +1,251 @@
+//! Read the numbers of a compressed record without decompressing the text.
+
+use std::fmt;
+
+use crate::zsan_parser::{Block, is_number_byte, is_single_block, retrave_blocks};
+use crate::{Token, Tokens, ZsanError};
+
+/// A number of the text, `mantissa / 10^decimal_places`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ZsanNumber {
+    /// Absolute value with the decimal point removed.
+    pub value: u64,
+    pub negative: bool,
+    /// Digits after the point, at most 15.
+    pub decimal_places: u8,
+}
+
+impl ZsanNumber {
+    /// Signed value with the decimal point removed, e.g. -1250 for `-12.50`.
+    pub fn mantissa(&self) -> i128 {
+        if self.negative {
+            -(self.value as i128)
+        } else {
+            self.value as i128
+        }
+    }
+
+    /// The value as `f64`, rounded for more than 15 significant digits.
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa() as f64 / 10_f64.powi(self.decimal_places as i32)
+    }
+}
+
+/// The number as it is written in the text.
+impl fmt::Display for ZsanNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = Vec::new();
+        crate::all_ascii::Number {
+            value: self.value,
+            negative: self.negative,
+            decimal_places: self.decimal_places,
+        }
+        .write(&mut text);
+        f.write_str(std::str::from_utf8(&text).unwrap())
+    }
+}
+
+/// Iterator over the numbers of a compressed record, see [`numbers`].
+#[derive(Debug, Clone)]
+pub struct Numbers<'a> {
+    tokens: std::iter::Peekable<Tokens<'a>>,
+    /// 已经解析出但还没有返回的数字
+    literal_numbers: std::vec::IntoIter<ZsanNumber>,
+    /// 还没有解析的原文, 可能还会接上之后的token
+    pending: Vec<u8>,
+}
+
+/// The numbers of the text of a compressed record, in order, without decompressing
+/// it.
+///
+/// These are the numbers `compress` finds in the text: an optional `-`, digits and
+/// an optional fraction, at most 18 digits each. Numbers encoded as tokens are
+/// decoded from their varint, only runs of literal bytes are parsed. A token that
+/// covers only part of a number, as an optimal parse may write, is decoded and
+/// parsed together with the bytes around it. A malformed
+/// token is yielded as an error and ends the iteration, the checksum is not verified.
+///
+/// ```
+/// let mut compressed = Vec::new();
+/// zsan::compress("6224      ABC20200902       -1312.25", &mut compressed);
+///
+/// let price = zsan::nth_number(&compressed, 2).unwrap().unwrap();
+/// assert_eq!(price.mantissa(), -131225);
+/// assert_eq!(price.to_string(), "-1312.25");
+/// assert_eq!(zsan::numbers(&compressed).unwrap().count(), 3);
+/// ```
+pub fn numbers(input: &[u8]) -> Result<Numbers<'_>, ZsanError> {
+    Ok(Numbers {
+        tokens: Tokens::new(input)?.peekable(),
+        literal_numbers: Vec::new().into_iter(),
+        pending: Vec::new(),
+    })
+}
+
+/// The `n`th number of the text, counting from 0, see [`numbers`].
+pub fn nth_number(input: &[u8], n: usize) -> Result<Option<ZsanNumber>, ZsanError> {
+    numbers(input)?.nth(n).transpose()
+}
+
+impl Iterator for Numbers<'_> {
+    type Item = Result<ZsanNumber, ZsanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(number) = self.literal_numbers.next() {
+                return Some(Ok(number));
+            }
+            // 原文的结尾, 出错时也先返回之前的数字
+            if !self.pending.is_empty() && matches!(self.tokens.peek(), None | Some(Err(_))) {
+                self.literal_numbers = parse_numbers(&self.pending).into_iter();
+                self.pending.clear();
+                continue;
+            }
+            match self.tokens.next()? {
+                Ok((
+                    _,
+                    Token::Number {
+                        value,
+                        negative,
+                        decimal_places,
+                    },
+                )) if self.pending.is_empty()
+                    && is_single_block(value, decimal_places)
+                    && self.next_starts_block() =>
+                {
+                    return Some(Ok(ZsanNumber {
+                        value,
+                        negative,
+                        decimal_places,
+                    }));
+                }
+                Ok((_, token)) => {
+                    token.write(&mut self.pending);
+                    // 不属于数字的字节之后解析器重新开始, 到这里为止的原文可以单独解析
+                    if let Some(end) = self.pending.iter().rposition(|&b| !is_number_byte(b)) {
+                        self.literal_numbers = parse_numbers(&self.pending[..=end]).into_iter();
+                        self.pending.drain(..=end);
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl Numbers<'_> {
+    /// 下一个token的原文是否以不能接在数字之后的字节开始
+    fn next_starts_block(&mut self) -> bool {
+        match self.tokens.peek() {
+            None | Some(Err(_)) => true,
+            Some(Ok((_, Token::Literal(bytes)))) => bytes
+                .first()
+                .is_some_and(|&b| !b.is_ascii_digit() && b != b'.'),
+            Some(Ok((_, Token::Spaces(count)))) => *count > 0,
+            Some(Ok((_, Token::Number { negative, .. }))) => *negative,
+        }
+    }
+}
+
+impl std::iter::FusedIterator for Numbers<'_> {}
+
+fn parse_numbers(text: &[u8]) -> Vec<ZsanNumber> {
+    let (_, _, blocks) = retrave_blocks(text);
+    blocks
+        .into_iter()
+        .filter_map(|block| match block {
+            Block::Numerical(_, _, number) => Some(ZsanNumber {
+                value: number.base,
+                negative: number.negative,
+                decimal_places: number.decimal_places as u8,
+            }),
+            Block::Space(..) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ZsanNumber, nth_number, numbers, parse_numbers};
+    use crate::{Compressor, ZsanError};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_numbers() {
+        let input = "6224      ABC20200902 1 -7 0.5   -1312.25 x0y 00 9951";
+        let mut compressed = Vec::new();
+        crate::compress(input, &mut compressed);
+        let texts: Vec<String> = numbers(&compressed)
+            .unwrap()
+            .map(|number| number.unwrap().to_string())
+            .collect();
+        assert_eq!(
+            texts,
+            ["6224", "20200902", "1", "-7", "0.5", "-1312.25", "9951"]
+        );
+
+        let number = nth_number(&compressed, 5).unwrap().unwrap();
+        assert_eq!(
+            number,
+            ZsanNumber {
+                value: 131225,
+                negative: true,
+                decimal_places: 2,
+            }
+        );
+        assert_eq!(number.mantissa(), -131225);
+        assert_eq!(number.to_f64(), -1312.25);
+        assert_eq!(nth_number(&compressed, 7), Ok(None));
+        assert_eq!(nth_number(&[], 0), Ok(None));
+
+        // 原样存储的记录解析原文
+        let mut stored = vec![0x48];
+        stored.extend_from_slice("价格 -12.5".as_bytes());
+        assert_eq!(
+            nth_number(&stored, 0).unwrap().unwrap().to_string(),
+            "-12.5"
+        );
+
+        assert_eq!(
+            numbers(&[0x40, b'1', 0x7f]).unwrap().collect::<Vec<_>>(),
+            [
+                Ok(ZsanNumber {
+                    value: 1,
+                    negative: false,
+                    decimal_places: 0,
+                }),
+                Err(ZsanError::InvalidToken(1)),
+            ]
+        );
+        assert_eq!(nth_number(b"1", 0), Err(ZsanError::InvalidHeader(b'1')));
+
+        // 字面量 `6` 之后的token `5` 是同一个数字的一部分
+        let texts: Vec<String> = numbers(&[0x40, b'6', 0xc5, b' ', 0xc7])
+            .unwrap()
+            .map(|number| number.unwrap().to_string())
+            .collect();
+        assert_eq!(texts, ["65", "7"]);
+    }
+
+    proptest! {
+        #[test]
+        fn same_as_parsing_the_text(
+            input in "[0-9 .\\-A]{0,200}|\\PC{0,50}",
+            checksum in any::<bool>(),
+            entropy in any::<bool>(),
+            optimal in any::<bool>(),
+            min_number_len in 0..6_usize,
+        ) {
+            let mut compressed = Vec::new();
+            Compressor::builder()
+                .checksum(checksum)
+                .entropy(entropy)
+                .optimal_parse(optimal)
+                .min_number_len(min_number_len)
+                .build()
+                .compress(&input, &mut compressed);
+            let found: Vec<ZsanNumber> = numbers(&compressed).unwrap().map(Result::unwrap).collect();
+            prop_assert_eq!(found, parse_numbers(input.as_bytes()));
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Optimal parse: the shortest token stream for a record.
+//!
+//! Every number that decodes to exactly the bytes at some position is a candidate
+//! token, not only the numbers `retrave_blocks` finds: a run of 20 digits can be one
+//! token, and a number the encoding mode cannot represent can leave its sign as a
+//! literal. For each encoding mode a dynamic program over the positions of the
+//! record finds the cheapest way to cover the rest of the record, using the exact
+//! encoded size of every candidate.
+
+use crate::all_ascii::space::compress_space;
+use crate::header::*;
+use crate::zsan::numerical_compressor;
+use crate::zsan_parser::MAX_DECIMAL_PLACES;
+use crate::{Compressor, TieBreak};
+
+/// 从一个位置开始的一步
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Literal,
+    Spaces(usize),
+    Number(Candidate),
+}
+
+/// 解码后与原文的这一段完全相同的数字
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    len: usize,
+    value: u64,
+    negative: bool,
+    decimal_places: u8,
+}
+
+/// 追加 `src` 最短的token流, 包括首字节. `src` 只包含可以作为字面量的字节
+pub(crate) fn compress_tokens(src: &[u8], options: &Compressor, out: &mut Vec<u8>) {
+    let n = src.len();
+    // 从每个位置开始的空格数
+    let mut spaces = vec![0; n + 1];
+    for i in (0..n).rev() {
+        if src[i] == b' ' {
+            spaces[i] = spaces[i + 1] + 1;
+        }
+    }
+    // 每个位置允许的数字候选, 与编码方式无关. 位置 `i` 的候选是 `candidates[starts[i]..starts[i + 1]]`
+    let (mut candidates, mut starts, mut scratch) =
+        (Vec::new(), Vec::with_capacity(n + 1), Vec::new());
+    for i in 0..n {
+        starts.push(candidates.len());
+        scratch.clear();
+        number_candidates(src, i, &mut scratch);
+        candidates.extend(
+            scratch
+                .iter()
+                .filter(|c| options.accepts_number(c.len, c.negative, c.decimal_places as usize)),
+        );
+    }
+    starts.push(candidates.len());
+
+    let mut best: Option<(u8, Cost, Vec<Step>)> = None;
+    for mode in [
+        FIRST_BYTE_UNSIGNED_INTEGER,
+        FIRST_BYTE_INTEGER,
+        FIRST_BYTE_UNSIGNED_DECIMAL,
+        FIRST_BYTE_DECIMAL,
+    ] {
+        let (cost, steps) = shortest(src, options, mode, &spaces, &candidates, &starts);
+        if best.as_ref().is_none_or(|best| cost < best.1) {
+            best = Some((mode, cost, steps));
+        }
+    }
+    let (mode, _, steps) = best.unwrap();
+
+    let numerical_compressor = numerical_compressor(mode);
+    out.push(mode | ZSAN_FLAG_MASK);
+    let mut i = 0;
+    while i < n {
+        i += match steps[i] {
+            Step::Literal => {
+                out.push(src[i]);
+                1
+            }
+            Step::Spaces(count) => {
+                compress_space(count, out);
+                count
+            }
+            Step::Number(c) => {
+                numerical_compressor(c.value, c.negative, c.decimal_places, out);
+                c.len
+            }
+        };
+    }
+}
+
+/// 比较划分的代价: 字节数, 然后按 `tie_break` 是token数或字面量字节数, 最后是步数.
+/// 字节数相同时少拆开数字, 例如不会把 `6224` 写成字面量 `6` 和token `224`
+type Cost = (usize, usize, usize);
+
+/// 一种编码方式下token流的最小代价, 以及每个位置开始的一步.
+///
+/// 从后往前计算, `cost[i]` 是 `src[i..]` 的最小代价. 空格只考虑到空格串结尾的token:
+/// 较短的token之后剩下的空格至少还要同样多的字节
+fn shortest(
+    src: &[u8],
+    options: &Compressor,
+    mode: u8,
+    spaces: &[usize],
+    candidates: &[Candidate],
+    starts: &[usize],
+) -> (Cost, Vec<Step>) {
+    let n = src.len();
+    let numerical_compressor = numerical_compressor(mode);
+    let (literal, token) = match options.tie_break {
+        TieBreak::Literal => (0, 1),
+        TieBreak::Token => (1, 0),
+    };
+    let add = |(bytes, tie, steps): Cost, len: usize, tie_cost: usize| {
+        (bytes + len, tie + tie_cost, steps + 1)
+    };
+
+    let mut cost = vec![(0, 0, 0); n + 1];
+    let mut steps = vec![Step::Literal; n];
+    let mut scratch = Vec::with_capacity(16);
+    for i in (0..n).rev() {
+        let mut best = (add(cost[i + 1], 1, literal), Step::Literal);
+        let count = spaces[i];
+        if count > 0 && options.accepts_space(count) {
+            scratch.clear();
+            compress_space(count, &mut scratch);
+            let total = add(cost[i + count], scratch.len(), token);
+            if total < best.0 {
+                best = (total, Step::Spaces(count));
+            }
+        }
+        for &c in &candidates[starts[i]..starts[i + 1]] {
+            scratch.clear();
+            if numerical_compressor(c.value, c.negative, c.decimal_places, &mut scratch) {
+                let total = add(cost[i + c.len], scratch.len(), token);
+                if total < best.0 {
+                    best = (total, Step::Number(c));
+                }
+            }
+        }
+        (cost[i], steps[i]) = best;
+    }
+    (cost[0], steps)
+}
+
+/// 从 `start` 开始的所有数字候选, 按长度从短到长
+fn number_candidates(src: &[u8], start: usize, out: &mut Vec<Candidate>) {
+    let negative = src[start] == b'-';
+    let integer_start = start + negative as usize;
+    let digit_end = |from: usize| {
+        from + src[from.min(src.len())..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let integer_end = digit_end(integer_start);
+    if integer_end == integer_start {
+        return;
+    }
+
+    let mut value = 0_u64;
+    let integer_end = if src[integer_start] == b'0' {
+        // 整数部分只能是一个0, 没有小数时不产生任何字符
+        integer_start + 1
+    } else {
+        for (i, &b) in src.iter().enumerate().take(integer_end).skip(integer_start) {
+            let Some(next) = value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((b - b'0') as u64))
+            else {
+                return;
+            };
+            value = next;
+            out.push(Candidate {
+                len: i + 1 - start,
+                value,
+                negative,
+                decimal_places: 0,
+            });
+        }
+        integer_end
+    };
+
+    if src.get(integer_end) != Some(&b'.') {
+        return;
+    }
+    let fraction_end = digit_end(integer_end + 1).min(integer_end + 1 + MAX_DECIMAL_PLACES);
+    for (i, &b) in src
+        .iter()
+        .enumerate()
+        .take(fraction_end)
+        .skip(integer_end + 1)
+    {
+        let Some(next) = value
+            .checked_mul(10)
+            .and_then(|v| v.checked_add((b - b'0') as u64))
+        else {
+            return;
+        };
+        value = next;
+        out.push(Candidate {
+            len: i + 1 - start,
+            value,
+            negative,
+            decimal_places: (i - integer_end) as u8,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compress_tokens;
+    use crate::all_ascii::Number;
+    use crate::all_ascii::space::compress_space;
+    use crate::header::*;
+    use crate::zsan::numerical_compressor;
+    use crate::{Compressor, TieBreak};
+    use proptest::prelude::*;
+
+    /// 穷举所有划分: 任意长度的空格token, 以及解码后与原文相同的任意数字token
+    fn brute_force(src: &[u8], options: &Compressor, mode: u8, i: usize) -> usize {
+        if i == src.len() {
+            return 0;
+        }
+        let mut best = 1 + brute_force(src, options, mode, i + 1);
+        let mut scratch = Vec::new();
+        for end in i + 1..=src.len() {
+            let text = &src[i..end];
+            if text.iter().all(|&b| b == b' ') && options.accepts_space(text.len()) {
+                scratch.clear();
+                compress_space(text.len(), &mut scratch);
+                best = best.min(scratch.len() + brute_force(src, options, mode, end));
+            }
+            let Some(number) = parse(text) else {
+                continue;
+            };
+            scratch.clear();
+            if options.accepts_number(text.len(), number.negative, number.decimal_places as usize)
+                && numerical_compressor(mode)(
+                    number.value,
+                    number.negative,
+                    number.decimal_places,
+                    &mut scratch,
+                )
+            {
+                best = best.min(scratch.len() + brute_force(src, options, mode, end));
+            }
+        }
+        best
+    }
+
+    /// 写出来恰好是 `text` 的数字
+    fn parse(text: &[u8]) -> Option<Number> {
+        let negative = text.first() == Some(&b'-');
+        let digits = &text[negative as usize..];
+        let (integer, fraction) = match digits.iter().position(|&b| b == b'.') {
+            Some(point) => (&digits[..point], &digits[point + 1..]),
+            None => (digits, &[][..]),
+        };
+        let mut value = 0_u64;
+        for &b in integer.iter().chain(fraction) {
+            if !b.is_ascii_digit() {
+                return None;
+            }
+            value = value.checked_mul(10)?.checked_add((b - b'0') as u64)?;
+        }
+        let number = Number {
+            value,
+            negative,
+            decimal_places: u8::try_from(fraction.len()).ok().filter(|&dp| dp <= 15)?,
+        };
+        let mut written = Vec::new();
+        number.write(&mut written);
+        (written == text).then_some(number)
+    }
+
+    proptest! {
+        #[test]
+        fn same_as_brute_force(
+            input in "[0-9 .\\-A]{0,10}",
+            min_number_len in 0..4_usize,
+            min_space_run in 1..3_usize,
+            [spaces, integers, negatives, decimals, token] in any::<[bool; 5]>(),
+        ) {
+            let options = Compressor::builder()
+                .min_number_len(min_number_len)
+                .min_space_run(min_space_run)
+                .spaces(spaces)
+                .integers(integers)
+                .negatives(negatives)
+                .decimals(decimals)
+                .tie_break(if token { TieBreak::Token } else { TieBreak::Literal })
+                .build();
+            let src = input.as_bytes();
+            let expected = [
+                FIRST_BYTE_UNSIGNED_INTEGER,
+                FIRST_BYTE_INTEGER,
+                FIRST_BYTE_UNSIGNED_DECIMAL,
+                FIRST_BYTE_DECIMAL,
+            ]
+            .into_iter()
+            .map(|mode| brute_force(src, &options, mode, 0))
+            .min()
+            .unwrap();
+
+            let mut out = Vec::new();
+            compress_tokens(src, &options, &mut out);
+            prop_assert_eq!(out.len() - 1, expected);
+            let mut text = Vec::new();
+            crate::decompress(&out, &mut text).unwrap();
+            prop_assert_eq!(text, src);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Order-preserving encoding, see [`crate::CompressorBuilder::order_preserving`].
+//!
+//! Comparing two encoded records byte by byte gives the same result as comparing the
+//! texts. Every token starts with the first byte of its text, so tokens compare with
+//! each other and with literals like the text does:
+//!
+//! - Bytes other than spaces and digits are literals, copied as is.
+//! - A run of spaces is `0x20` followed by its length. When the text goes on with a
+//!   byte greater than a space, a longer run is the smaller text, so the length is
+//!   written in descending order. When the text ends or goes on with a smaller byte,
+//!   the length is written in ascending order, below all descending lengths.
+//! - A run of digits is its first digit followed by the other digits, two per byte,
+//!   and a terminator that sorts below the digits when the text ends or goes on with
+//!   a byte below `'0'`, and above them otherwise.
+//!
+//! Numbers are encoded by their digits and not by value: as text `"9"` sorts after
+//! `"10"`.
+
+use crate::ZsanError;
+use crate::header::*;
+
+/// 空格串的标记, 即空格本身
+const SPACE_MARK: u8 = b' ';
+
+/// 升序长度编码: 首字节 0..=0x77 直接表示长度 1..=120,
+/// 0x78 后面一个字节表示 121..=376, 0x79 后面两个字节 (大端) 表示 377..=65912
+const SHORT_RUN_LIMIT: u8 = 0x78;
+const ONE_BYTE_RUN: u8 = 0x78;
+const TWO_BYTE_RUN: u8 = 0x79;
+const ONE_BYTE_BASE: usize = SHORT_RUN_LIMIT as usize + 1;
+const TWO_BYTE_BASE: usize = ONE_BYTE_BASE + 0x100;
+/// 更长的空格串拆开, 后面跟着空格的部分长度是升序, 不影响顺序
+const MAX_RUN: usize = TWO_BYTE_BASE + 0xffff;
+/// 降序长度的每个字节取反, 首字节都在 0x80 以上
+const DESCENDING_MASK: u8 = 0xff;
+
+/// 数字串的结束符和数字组成的符号按 低结束 < '0'..'9' < 高结束 排序,
+/// 每个字节编码两个符号, 或者单独一个结束符
+const LOW_END: u8 = 0;
+const HIGH_END: u8 = 121;
+/// 一个数字加上后面的符号 (低结束, 10个数字, 高结束) 共有 10 * 12 种组合
+const SYMBOLS: u8 = 12;
+
+/// 写入头部和保序编码的 `src`
+pub(crate) fn compress(src: &[u8], out: &mut Vec<u8>) {
+    out.push(EXTENSION_FLAG | ZSAN_FLAG_MASK);
+    out.push(ORDERED_FEATURE as u8);
+
+    let mut i = 0;
+    while i < src.len() {
+        let b = src[i];
+        if b == b' ' {
+            let end = run_end(src, i, |b| b == b' ').min(i + MAX_RUN);
+            out.push(SPACE_MARK);
+            let descending = src.get(end).is_some_and(|&next| next > b' ');
+            write_run_len(end - i, descending, out);
+            i = end;
+        } else if b.is_ascii_digit() {
+            let end = run_end(src, i, |b| b.is_ascii_digit());
+            out.push(b);
+            let high = src.get(end).is_some_and(|&next| next > b'9');
+            write_digits(&src[i + 1..end], high, out);
+            i = end;
+        } else {
+            out.push(b);
+            i += 1;
+        }
+    }
+}
+
+fn run_end(src: &[u8], start: usize, accepts: impl Fn(u8) -> bool) -> usize {
+    src[start..]
+        .iter()
+        .position(|&b| !accepts(b))
+        .map_or(src.len(), |len| start + len)
+}
+
+fn write_run_len(len: usize, descending: bool, out: &mut Vec<u8>) {
+    let start = out.len();
+    if len < ONE_BYTE_BASE {
+        out.push((len - 1) as u8);
+    } else if len < TWO_BYTE_BASE {
+        out.extend_from_slice(&[ONE_BYTE_RUN, (len - ONE_BYTE_BASE) as u8]);
+    } else {
+        out.push(TWO_BYTE_RUN);
+        out.extend_from_slice(&((len - TWO_BYTE_BASE) as u16).to_be_bytes());
+    }
+    if descending {
+        for b in &mut out[start..] {
+            *b ^= DESCENDING_MASK;
+        }
+    }
+}
+
+/// 返回长度, 是否降序和占用的字节数
+fn read_run_len(input: &[u8]) -> Option<(usize, bool, usize)> {
+    let &first = input.first()?;
+    let descending = first > 0x7f;
+    let mask = if descending { DESCENDING_MASK } else { 0 };
+    let byte = |i: usize| input.get(i).map(|&b| (b ^ mask) as usize);
+    match first ^ mask {
+        short @ ..SHORT_RUN_LIMIT => Some((short as usize + 1, descending, 1)),
+        ONE_BYTE_RUN => Some((ONE_BYTE_BASE + byte(1)?, descending, 2)),
+        TWO_BYTE_RUN => Some((TWO_BYTE_BASE + (byte(1)? << 8 | byte(2)?), descending, 3)),
+        _ => None,
+    }
+}
+
+fn write_digits(digits: &[u8], high: bool, out: &mut Vec<u8>) {
+    let end = if high { SYMBOLS - 1 } else { 0 };
+    let mut pairs = digits.chunks_exact(2);
+    for pair in &mut pairs {
+        let (first, second) = (pair[0] - b'0', pair[1] - b'0' + 1);
+        out.push(1 + first * SYMBOLS + second);
+    }
+    match pairs.remainder() {
+        [digit] => out.push(1 + (digit - b'0') * SYMBOLS + end),
+        _ => out.push(if high { HIGH_END } else { LOW_END }),
+    }
+}
+
+/// 解码保序编码的内容, 追加到 `out`
+pub(crate) fn decompress(input: &[u8], out: &mut Vec<u8>) -> Result<(), ZsanError> {
+    let mut i = 0;
+    while i < input.len() {
+        let b = input[i];
+        if b == SPACE_MARK {
+            let (len, _, size) = read_run_len(&input[i + 1..]).ok_or(ZsanError::InvalidToken(i))?;
+            out.resize(out.len() + len, b' ');
+            i += 1 + size;
+        } else if b.is_ascii_digit() {
+            out.push(b);
+            i += 1 + read_digits(&input[i + 1..], out).ok_or(ZsanError::InvalidToken(i))?;
+        } else {
+            out.push(b);
+            i += 1;
+        }
+    }
+    Ok(())
+}
+
+/// 返回占用的字节数
+fn read_digits(input: &[u8], out: &mut Vec<u8>) -> Option<usize> {
+    for (i, &b) in input.iter().enumerate() {
+        match b {
+            LOW_END | HIGH_END => return Some(i + 1),
+            1..HIGH_END => {
+                let (first, second) = ((b - 1) / SYMBOLS, (b - 1) % SYMBOLS);
+                out.push(b'0' + first);
+                if second == 0 || second == SYMBOLS - 1 {
+                    return Some(i + 1);
+                }
+                out.push(b'0' + second - 1);
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_RUN, read_run_len, write_run_len};
+    use crate::{Compressor, ZsanError};
+    use proptest::prelude::*;
+
+    fn ordered() -> Compressor {
+        Compressor::builder().order_preserving(true).build()
+    }
+
+    fn encode(text: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        ordered().compress(text, &mut out);
+        let mut back = Vec::new();
+        crate::decompress(&out, &mut back).unwrap();
+        assert_eq!(back, text.as_bytes());
+        out
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(""), b"");
+        assert_eq!(encode("A"), [0xc0, 0x02, b'A']);
+        // "20200902": 首位数字, 三对数字, 最后一位和低结束符
+        assert_eq!(
+            encode("20200902 "),
+            [
+                0xc0,
+                0x02,
+                b'2',
+                1 + 3,
+                1 + 1,
+                1 + 9 * 12 + 1,
+                1 + 2 * 12,
+                b' ',
+                0
+            ]
+        );
+        // 空格后面是大于空格的字节, 长度降序
+        assert_eq!(encode("  A"), [0xc0, 0x02, b' ', !1, b'A']);
+
+        // 超长的空格串拆开
+        let long = format!("{}A", " ".repeat(MAX_RUN + 1));
+        assert_eq!(encode(&long)[2..], [b' ', 0x79, 0xff, 0xff, b' ', !0, b'A']);
+        assert!(encode(&long) < encode(&long[1..]));
+
+        // 不比原文长太多, 长的空格串和数字串变短
+        let text = "6224      ABC20200902       1312       -1145       7802.5 ";
+        assert!(encode(text).len() < text.len() * 2 / 3);
+    }
+
+    #[test]
+    fn test_order() {
+        let mut texts = [
+            "", " ", "  ", " \n", "  A", " A", "0", "00", "007", "1", "1.5", "10", "19", "1A", "9",
+            "99", "A", "A ", "A  ", "A 1", "A 10", "A 9", "A\t", "B", "价格",
+        ];
+        texts.sort();
+        let encoded: Vec<Vec<u8>> = texts.iter().map(|text| encode(text)).collect();
+        assert!(encoded.is_sorted(), "{texts:?}");
+    }
+
+    #[test]
+    fn test_run_len() {
+        for len in [1, 2, 120, 121, 376, 377, MAX_RUN] {
+            for descending in [false, true] {
+                let mut out = Vec::new();
+                write_run_len(len, descending, &mut out);
+                assert_eq!(read_run_len(&out), Some((len, descending, out.len())));
+            }
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        let decompress = |input: &[u8]| crate::decompress(input, &mut Vec::new());
+        assert_eq!(
+            decompress(&[0xc0, 0x02, b'A', b' ']),
+            Err(ZsanError::InvalidToken(1))
+        );
+        assert_eq!(
+            decompress(&[0xc0, 0x02, b'1', 1 + 1]),
+            Err(ZsanError::InvalidToken(0))
+        );
+        assert_eq!(
+            decompress(&[0xc0, 0x02, b'1', 122]),
+            Err(ZsanError::InvalidToken(0))
+        );
+        assert_eq!(
+            decompress(&[0xc0, 0x02, b' ', 0x7a]),
+            Err(ZsanError::InvalidToken(0))
+        );
+        // 保序编码的首字节不能带编码方式
+        assert_eq!(
+            decompress(&[0xc1, 0x02, b'A']),
+            Err(ZsanError::InvalidHeader(0xc1))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn preserves_order(a in "[0-9 .\\-A\\n]{0,40}|\\PC{0,20}", b in "[0-9 .\\-A\\n]{0,40}|\\PC{0,20}") {
+            prop_assert_eq!(encode(&a).cmp(&encode(&b)), a.cmp(&b));
+        }
+
+        #[test]
+        fn preserves_order_of_similar_keys(
+            prefix in "[0-9 A]{0,10}",
+            a in "[0-9 \\tA]{0,10}",
+            b in "[0-9 \\tA]{0,10}",
+        ) {
+            let (a, b) = (format!("{prefix}{a}"), format!("{prefix}{b}"));
+            prop_assert_eq!(encode(&a).cmp(&encode(&b)), a.cmp(&b));
+        }
+
+        #[test]
+        fn any_bytes_never_panic(tokens in prop::collection::vec(any::<u8>(), 0..64)) {
+            let mut input = vec![0xc0, 0x02];
+            input.extend_from_slice(&tokens);
+            let _ = crate::decompress(&input, &mut Vec::new());
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Multi-threaded frame compression, enabled with the `parallel` feature.
+
+use std::thread;
+
+use crate::frame::{compress_frame, next_frame, split_frames};
+use crate::{Compressor, ZsanError};
+
+/// 把 `items` 分成 `threads` 段, 每段在一个线程里按顺序处理, 结果按原来的顺序返回
+fn map_in_threads<T: Sync, R: Send>(
+    items: &[T],
+    threads: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    if items.len() <= 1 || threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let per_thread = items.len().div_ceil(threads);
+    let f = &f;
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(per_thread)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Same as [`Compressor::compress_parallel`] with the default [`Compressor`].
+pub fn compress_parallel(src: &str, threads: usize, out: &mut Vec<u8>) {
+    Compressor::default().compress_parallel(src, threads, out);
+}
+
+/// Decompress frames on `threads` threads and append the text to `out`, the
+/// multi-threaded version of [`crate::decompress_frames`].
+///
+/// Nothing is appended to `out` when an error is returned.
+pub fn decompress_parallel(
+    input: &[u8],
+    threads: usize,
+    out: &mut Vec<u8>,
+) -> Result<(), ZsanError> {
+    let mut frames = Vec::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let (payload, len) = next_frame(rest)?;
+        frames.push(payload);
+        rest = &rest[len..];
+    }
+
+    let texts = map_in_threads(&frames, threads, |payload| {
+        let mut text = Vec::new();
+        crate::decompress(payload, &mut text).map(|_| text)
+    });
+    let texts = texts.into_iter().collect::<Result<Vec<_>, _>>()?;
+    out.reserve(texts.iter().map(Vec::len).sum());
+    for text in texts {
+        out.extend_from_slice(&text);
+    }
+    Ok(())
+}
+
+impl Compressor {
+    /// Compress the frames of [`Compressor::compress_frames`] on `threads` threads.
+    ///
+    /// The output is byte for byte the same as `compress_frames` for any number of
+    /// threads, and is read back with [`crate::decompress_frames`] or
+    /// [`decompress_parallel`].
+    pub fn compress_parallel(&self, src: &str, threads: usize, out: &mut Vec<u8>) {
+        let frames: Vec<&str> = split_frames(src, self.frame_size).collect();
+        let compressed = map_in_threads(&frames, threads, |frame| {
+            let mut out = Vec::new();
+            compress_frame(self, frame, &mut out);
+            out
+        });
+        out.reserve(compressed.iter().map(Vec::len).sum());
+        for frame in compressed {
+            out.extend_from_slice(&frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress_parallel, decompress_parallel};
+    use crate::{Compressor, ZsanError, decompress_frames};
+
+    #[test]
+    fn test_same_output_for_any_thread_count() {
+        let src: String = (0..5000)
+            .map(|i| format!("{i:>8} EFG{:08}   -{}.{:03}\n", i * 31, i % 977, i % 1000))
+            .collect();
+        let compressor = Compressor::builder().frame_size(1000).build();
+        let mut expected = Vec::new();
+        compressor.compress_frames(&src, &mut expected);
+
+        for threads in [0, 1, 2, 3, 8, 1000] {
+            let mut out = Vec::new();
+            compressor.compress_parallel(&src, threads, &mut out);
+            assert_eq!(out, expected, "{threads} threads");
+
+            let mut text = Vec::new();
+            decompress_parallel(&out, threads, &mut text).unwrap();
+            assert_eq!(text, src.as_bytes());
+        }
+
+        let mut out = Vec::new();
+        compress_parallel(&src, 4, &mut out);
+        let mut text = Vec::new();
+        decompress_frames(&out, &mut text).unwrap();
+        assert_eq!(text, src.as_bytes());
+    }
+
+    #[test]
+    fn test_invalid_frame() {
+        let mut out = Vec::new();
+        Compressor::builder()
+            .frame_size(1)
+            .build()
+            .compress_parallel("A  1\nB  2\nC  3\n", 2, &mut out);
+        // 第二帧的首字节
+        let second = 1 + out[0] as usize + 1;
+        out[second] = b'1';
+
+        let mut text = b"kept".to_vec();
+        assert_eq!(
+            decompress_parallel(&out, 2, &mut text),
+            Err(ZsanError::InvalidHeader(b'1'))
+        );
+        assert_eq!(text, b"kept");
+    }
+}
@@ -0,0 +1,171 @@
+//! Serialize strings as zsan compressed bytes, enabled with the `serde` feature.
+//!
+//! Use [`ZsanString`] as the field type, or keep a `String` field and add
+//! `#[serde(with = "zsan::serde")]`:
+//!
+//! ```
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Quote {
+//!     #[serde(with = "zsan::serde")]
+//!     line: String,
+//!     raw: zsan::ZsanString,
+//! }
+//! ```
+//!
+//! Values that are not valid zsan data, or do not decode to UTF-8, are reported as
+//! deserialization errors of the format.
+
+use std::fmt;
+use std::ops::Deref;
+
+use ::serde::de::{self, Deserializer, SeqAccess, Visitor};
+use ::serde::{Deserialize, Serialize, Serializer};
+
+/// A `String` that is serialized as zsan compressed bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ZsanString(pub String);
+
+impl ZsanString {
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl From<String> for ZsanString {
+    fn from(value: String) -> Self {
+        ZsanString(value)
+    }
+}
+
+impl From<&str> for ZsanString {
+    fn from(value: &str) -> Self {
+        ZsanString(value.to_owned())
+    }
+}
+
+impl From<ZsanString> for String {
+    fn from(value: ZsanString) -> Self {
+        value.0
+    }
+}
+
+impl Deref for ZsanString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ZsanString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for ZsanString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ZsanString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(ZsanString)
+    }
+}
+
+/// Serialize `value` as the bytes written by [`crate::compress`].
+pub fn serialize<S: Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut out = Vec::new();
+    crate::compress(value, &mut out);
+    serializer.serialize_bytes(&out)
+}
+
+/// Deserialize bytes written by [`serialize`] back to a `String`.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_bytes(CompressedVisitor)
+}
+
+struct CompressedVisitor;
+
+impl CompressedVisitor {
+    fn decode<E: de::Error>(input: &[u8]) -> Result<String, E> {
+        let mut out = Vec::new();
+        crate::decompress(input, &mut out).map_err(E::custom)?;
+        String::from_utf8(out).map_err(E::custom)
+    }
+}
+
+impl<'de> Visitor<'de> for CompressedVisitor {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("zsan compressed bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<String, E> {
+        Self::decode(v)
+    }
+
+    /// 不支持字节串的格式 (例如JSON) 把字节写成数组
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<String, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(b) = seq.next_element::<u8>()? {
+            bytes.push(b);
+        }
+        Self::decode(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZsanString;
+    use ::serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Quote {
+        #[serde(with = "crate::serde")]
+        line: String,
+        raw: ZsanString,
+    }
+
+    fn quote() -> Quote {
+        Quote {
+            line: "6224      ABC20200902       1312       -1145       7802.5 ".to_owned(),
+            raw: "9951      EFG0990     132230     132280".into(),
+        }
+    }
+
+    #[test]
+    fn test_cbor() {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&quote(), &mut bytes).unwrap();
+        let back: Quote = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(back, quote());
+        assert!(bytes.len() < quote().line.len() + quote().raw.len());
+    }
+
+    #[test]
+    fn test_json() {
+        let json = serde_json::to_string(&quote()).unwrap();
+        assert_eq!(serde_json::from_str::<Quote>(&json).unwrap(), quote());
+    }
+
+    #[test]
+    fn test_corrupt() {
+        // 0x7F不是合法的token
+        let json = r#"{"line":[64,65,127],"raw":[64,65]}"#;
+        let error = serde_json::from_str::<Quote>(json).unwrap_err();
+        assert!(error.to_string().contains("invalid token"), "{error}");
+
+        // 原样存储的内容不是UTF-8
+        let json = r#"{"line":[72,255],"raw":[64,65]}"#;
+        assert!(serde_json::from_str::<Quote>(json).is_err());
+
+        // JSON字符串按字节交给解码, '1' 不是合法的首字节
+        let json = r#"{"line":"1","raw":[64,65]}"#;
+        let error = serde_json::from_str::<Quote>(json).unwrap_err();
+        assert!(error.to_string().contains("invalid header"), "{error}");
+    }
+}
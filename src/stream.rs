@@ -0,0 +1,309 @@
+//! Incremental decompression of a record that arrives in pieces.
+
+use crate::ZsanError;
+use crate::checksum::crc32c_update;
+use crate::header::{CHECKSUM_FEATURE, CHECKSUM_LEN, Header, ORDERED_FEATURE};
+
+#[derive(Debug, Clone)]
+enum State {
+    /// 首字节和扩展字节还没有收完
+    Header,
+    Stored,
+    Tokens {
+        first_byte: u8,
+    },
+    /// 哈夫曼编码和保序编码不逐段解码, `pending` 保存包括头部在内的整条记录
+    Buffered,
+    Failed(ZsanError),
+}
+
+/// Decompresses one record fed in chunks of any size, e.g. as it is read from a
+/// socket.
+///
+/// Tokens split between chunks are kept until the rest arrives. The result is the
+/// same as [`crate::decompress`] on the whole record: the same text, or the same
+/// error.
+///
+/// ```
+/// let mut compressed = Vec::new();
+/// zsan::compress("ABC      1234567", &mut compressed);
+///
+/// let mut decoder = zsan::Decoder::new();
+/// let mut text = Vec::new();
+/// for chunk in compressed.chunks(2) {
+///     decoder.feed(chunk, &mut text).unwrap();
+/// }
+/// decoder.finish(&mut text).unwrap();
+/// assert_eq!(text, b"ABC      1234567");
+/// ```
+///
+/// Records with the checksum feature are verified by [`Decoder::finish`], after the
+/// text has been produced. Huffman coded records, see [`crate::compress_entropy`],
+/// and order-preserving records are buffered and decoded by `finish`.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    state: State,
+    /// 收到但还没有解码的字节
+    pending: Vec<u8>,
+    /// 末尾的校验和不是token, 解码时保留这么多字节
+    holdback: usize,
+    /// 已经解码的token流长度
+    offset: usize,
+    crc: u32,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder {
+            state: State::Header,
+            pending: Vec::new(),
+            holdback: 0,
+            offset: 0,
+            crc: 0,
+        }
+    }
+
+    /// Decode as much of `input` as possible, append the text to `out` and return the
+    /// number of bytes appended.
+    ///
+    /// Nothing is appended when an error is returned, and every later call returns
+    /// the same error.
+    pub fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<usize, ZsanError> {
+        if let State::Failed(e) = &self.state {
+            return Err(e.clone());
+        }
+        let start = out.len();
+        self.pending.extend_from_slice(input);
+        match self.decode_pending(out) {
+            Ok(()) => Ok(out.len() - start),
+            Err(e) => Err(self.fail(e, out, start)),
+        }
+    }
+
+    /// Decode the rest of the record after the last [`Decoder::feed`], append the
+    /// text to `out` and return the number of bytes appended.
+    ///
+    /// Fails if the record is incomplete or, for records with the checksum feature,
+    /// if the checksum does not match the text produced by all calls.
+    pub fn finish(mut self, out: &mut Vec<u8>) -> Result<usize, ZsanError> {
+        let start = out.len();
+        match self.finish_pending(out) {
+            Ok(()) => Ok(out.len() - start),
+            Err(e) => Err(self.fail(e, out, start)),
+        }
+    }
+
+    fn fail(&mut self, e: ZsanError, out: &mut Vec<u8>, start: usize) -> ZsanError {
+        out.truncate(start);
+        self.state = State::Failed(e.clone());
+        e
+    }
+
+    fn decode_pending(&mut self, out: &mut Vec<u8>) -> Result<(), ZsanError> {
+        if let State::Header = self.state {
+            if self.pending.is_empty() {
+                return Ok(());
+            }
+            let header = match Header::parse(&self.pending) {
+                Err(ZsanError::Truncated) => return Ok(()),
+                result => result?,
+            };
+            if header.features & CHECKSUM_FEATURE != 0 {
+                self.holdback = CHECKSUM_LEN;
+            }
+            if header.entropy || header.features & ORDERED_FEATURE != 0 {
+                self.state = State::Buffered;
+                return Ok(());
+            }
+            self.pending.drain(..header.len);
+            self.state = if header.stored {
+                State::Stored
+            } else {
+                State::Tokens {
+                    first_byte: header.first_byte,
+                }
+            };
+        }
+
+        let available = self.pending.len().saturating_sub(self.holdback);
+        let text_start = out.len();
+        let consumed = match self.state {
+            State::Stored => {
+                out.extend_from_slice(&self.pending[..available]);
+                available
+            }
+            State::Tokens { first_byte } => crate::zsan::decompress_token_prefix(
+                first_byte,
+                &self.pending[..available],
+                self.offset,
+                false,
+                out,
+            )?,
+            _ => return Ok(()),
+        };
+        self.crc = crc32c_update(self.crc, &out[text_start..]);
+        self.offset += consumed;
+        self.pending.drain(..consumed);
+        Ok(())
+    }
+
+    fn finish_pending(&mut self, out: &mut Vec<u8>) -> Result<(), ZsanError> {
+        match self.state {
+            State::Failed(ref e) => return Err(e.clone()),
+            State::Header if self.pending.is_empty() => return Ok(()),
+            State::Header => return Err(ZsanError::Truncated),
+            State::Buffered => return crate::decompress(&self.pending, out),
+            State::Stored => {}
+            State::Tokens { first_byte } => {
+                let available = self.pending.len().saturating_sub(self.holdback);
+                let text_start = out.len();
+                crate::zsan::decompress_token_prefix(
+                    first_byte,
+                    &self.pending[..available],
+                    self.offset,
+                    true,
+                    out,
+                )?;
+                self.crc = crc32c_update(self.crc, &out[text_start..]);
+                self.pending.drain(..available);
+            }
+        }
+
+        if self.pending.len() < self.holdback {
+            return Err(ZsanError::Truncated);
+        }
+        if self.holdback > 0 {
+            let expected = u32::from_le_bytes(self.pending[..CHECKSUM_LEN].try_into().unwrap());
+            if expected != self.crc {
+                return Err(ZsanError::ChecksumMismatch {
+                    expected,
+                    actual: self.crc,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decoder;
+    use crate::{Compressor, ZsanError};
+    use proptest::prelude::*;
+
+    /// 按 `sizes` 循环切分输入, 逐块解码
+    fn decode_in_chunks(input: &[u8], sizes: &[usize]) -> Result<Vec<u8>, ZsanError> {
+        let mut decoder = Decoder::new();
+        let mut out = Vec::new();
+        let mut rest = input;
+        for &size in sizes.iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at(size.clamp(1, rest.len()));
+            let before = out.len();
+            let produced = decoder.feed(chunk, &mut out)?;
+            assert_eq!(produced, out.len() - before);
+            rest = tail;
+        }
+        decoder.finish(&mut out)?;
+        Ok(out)
+    }
+
+    fn decompress(input: &[u8]) -> Result<Vec<u8>, ZsanError> {
+        let mut out = Vec::new();
+        crate::decompress(input, &mut out).map(|_| out)
+    }
+
+    #[test]
+    fn test_split_tokens() {
+        let input = "6224      ABC20200902       -1312.25       1145       -7802 ";
+        for compressor in [
+            Compressor::default(),
+            Compressor::builder().checksum(true).build(),
+            Compressor::builder().entropy(true).checksum(true).build(),
+            Compressor::builder().order_preserving(true).build(),
+        ] {
+            let mut compressed = Vec::new();
+            compressor.compress(&input.repeat(10), &mut compressed);
+            for size in 1..8 {
+                let text = decode_in_chunks(&compressed, &[size]).unwrap();
+                assert_eq!(text, input.repeat(10).as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        // 不完整的varint
+        assert_eq!(
+            decode_in_chunks(&[0x40, b'A', 0b_1110_0000], &[1]),
+            Err(ZsanError::InvalidToken(1))
+        );
+        assert_eq!(decode_in_chunks(&[0xc0], &[1]), Err(ZsanError::Truncated));
+        assert_eq!(
+            decode_in_chunks(&[0xc0, 0x01, 0x48, b'A'], &[1]),
+            Err(ZsanError::Truncated)
+        );
+
+        // 出错之后一直返回同一个错误
+        let mut decoder = Decoder::new();
+        let mut out = Vec::new();
+        assert_eq!(
+            decoder.feed(&[0x40, b'A', 0x7f], &mut out),
+            Err(ZsanError::InvalidToken(1))
+        );
+        assert!(out.is_empty());
+        assert_eq!(
+            decoder.feed(b"B", &mut out),
+            Err(ZsanError::InvalidToken(1))
+        );
+        assert_eq!(decoder.finish(&mut out), Err(ZsanError::InvalidToken(1)));
+    }
+
+    proptest! {
+        #[test]
+        fn same_as_decompress(
+            input in "[0-9 .\\-A]{0,200}",
+            checksum in any::<bool>(),
+            entropy in any::<bool>(),
+            sizes in prop::collection::vec(1..20_usize, 1..5),
+        ) {
+            let mut compressed = Vec::new();
+            Compressor::builder()
+                .checksum(checksum)
+                .entropy(entropy)
+                .build()
+                .compress(&input, &mut compressed);
+            prop_assert_eq!(decode_in_chunks(&compressed, &sizes), Ok(input.into_bytes()));
+        }
+
+        #[test]
+        fn same_errors_as_decompress(
+            input in prop::collection::vec(any::<u8>(), 0..64),
+            sizes in prop::collection::vec(1..20_usize, 1..5),
+        ) {
+            prop_assert_eq!(decode_in_chunks(&input, &sizes), decompress(&input));
+        }
+
+        #[test]
+        fn same_errors_on_token_streams(
+            first_byte in prop::sample::select(vec![0x40_u8, 0x41, 0x42, 0x43, 0xc0]),
+            tokens in prop::collection::vec(any::<u8>(), 0..64),
+            sizes in prop::collection::vec(1..20_usize, 1..5),
+        ) {
+            let mut input = vec![first_byte];
+            if first_byte & 0x80 != 0 {
+                input.push(0x01);
+            }
+            input.extend_from_slice(&tokens);
+            prop_assert_eq!(decode_in_chunks(&input, &sizes), decompress(&input));
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Iterate over the tokens of a compressed record without decompressing it.
+
+use std::borrow::Cow;
+
+use crate::ZsanError;
+use crate::header::Header;
+use crate::zsan::{LITERAL_LIMIT, NumericalDecoder, Record, numerical_decoder};
+
+/// One token of a compressed record, see [`Tokens`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// Bytes copied to the text as is. Consecutive literal bytes are one token.
+    Literal(Cow<'a, [u8]>),
+    /// A run of spaces. Consecutive space tokens are merged.
+    Spaces(usize),
+    /// A number, written with exactly `decimal_places` digits after the point.
+    Number {
+        value: u64,
+        negative: bool,
+        decimal_places: u8,
+    },
+}
+
+impl Token<'_> {
+    /// Append the text of the token to `out`, the same bytes [`crate::decompress`]
+    /// produces for it.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Token::Literal(bytes) => out.extend_from_slice(bytes),
+            Token::Spaces(count) => out.resize(out.len() + count, b' '),
+            &Token::Number {
+                value,
+                negative,
+                decimal_places,
+            } => crate::all_ascii::Number {
+                value,
+                negative,
+                decimal_places,
+            }
+            .write(out),
+        }
+    }
+}
+
+/// Iterator over the tokens of a compressed record, yielding each token with its
+/// offset in the token stream.
+///
+/// ```
+/// use zsan::Token;
+///
+/// let mut compressed = Vec::new();
+/// zsan::compress("ABC      -12.5", &mut compressed);
+///
+/// let tokens: Vec<_> = zsan::Tokens::new(&compressed)
+///     .unwrap()
+///     .map(|token| token.unwrap().1)
+///     .collect();
+/// assert_eq!(tokens[0], Token::Literal(b"ABC"[..].into()));
+/// assert_eq!(tokens[1], Token::Spaces(6));
+/// assert_eq!(
+///     tokens[2],
+///     Token::Number { value: 125, negative: true, decimal_places: 1 }
+/// );
+/// ```
+///
+/// Offsets are the same as in [`ZsanError::InvalidToken`]: positions after the
+/// header, and after Huffman decoding for records written by
+/// [`crate::compress_entropy`]. A record stored as is yields its whole text as one
+/// literal. A malformed token is yielded as an error and ends the iteration. The
+/// checksum is not verified, as that needs the text.
+#[derive(Debug, Clone)]
+pub struct Tokens<'a> {
+    /// token流, 哈夫曼编码的记录解码后保存在这里
+    stream: Cow<'a, [u8]>,
+    stored: bool,
+    decoder: NumericalDecoder,
+    index: usize,
+}
+
+impl<'a> Tokens<'a> {
+    /// Fails if the header of `input` is invalid or the Huffman coded token stream is
+    /// malformed.
+    pub fn new(input: &'a [u8]) -> Result<Self, ZsanError> {
+        if input.is_empty() {
+            return Ok(Tokens {
+                stream: Cow::Borrowed(input),
+                stored: true,
+                decoder: numerical_decoder(0),
+                index: 0,
+            });
+        }
+        let record = Record::parse(input)?;
+        Ok(Self::from_stream(record.payload, &record.header))
+    }
+
+    /// 已经解析过头部的token流
+    pub(crate) fn from_stream(stream: Cow<'a, [u8]>, header: &Header) -> Self {
+        Tokens {
+            stream,
+            stored: header.stored,
+            decoder: numerical_decoder(header.first_byte),
+            index: 0,
+        }
+    }
+
+    /// 借用的token流不需要复制
+    fn literal(&self, start: usize, end: usize) -> Cow<'a, [u8]> {
+        match self.stream {
+            Cow::Borrowed(stream) => Cow::Borrowed(&stream[start..end]),
+            Cow::Owned(ref stream) => Cow::Owned(stream[start..end].to_vec()),
+        }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<(usize, Token<'a>), ZsanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.index;
+        let stream = &self.stream[..];
+        let &b = stream.get(start)?;
+
+        if self.stored {
+            self.index = stream.len();
+            return Some(Ok((
+                start,
+                Token::Literal(self.literal(start, stream.len())),
+            )));
+        }
+
+        let token = if b < LITERAL_LIMIT {
+            let len = stream[start..]
+                .iter()
+                .position(|&b| b >= LITERAL_LIMIT)
+                .unwrap_or(stream.len() - start);
+            self.index += len;
+            Token::Literal(self.literal(start, start + len))
+        } else if crate::all_ascii::is_space(b) {
+            let mut count = 0;
+            while let Some(&b) = stream
+                .get(self.index)
+                .filter(|&&b| crate::all_ascii::is_space(b))
+            {
+                count += crate::all_ascii::space::space_len(b);
+                self.index += 1;
+            }
+            Token::Spaces(count)
+        } else if let Some((number, len)) = crate::all_ascii::is_numerical(b)
+            .then(|| (self.decoder)(&stream[start..]))
+            .flatten()
+        {
+            self.index += len;
+            Token::Number {
+                value: number.value,
+                negative: number.negative,
+                decimal_places: number.decimal_places,
+            }
+        } else {
+            self.index = stream.len();
+            return Some(Err(ZsanError::InvalidToken(start)));
+        };
+        Some(Ok((start, token)))
+    }
+}
+
+impl std::iter::FusedIterator for Tokens<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Token, Tokens};
+    use crate::{Compressor, ZsanError};
+    use proptest::prelude::*;
+
+    fn tokens(input: &[u8]) -> Vec<Result<(usize, Token<'_>), ZsanError>> {
+        Tokens::new(input).unwrap().collect()
+    }
+
+    #[test]
+    fn test_tokens() {
+        let mut compressed = Vec::new();
+        crate::compress(&format!("A{}0.05 -7x", " ".repeat(70)), &mut compressed);
+        assert_eq!(
+            tokens(&compressed),
+            [
+                Ok((0, Token::Literal(b"A"[..].into()))),
+                Ok((1, Token::Spaces(70))),
+                Ok((
+                    3,
+                    Token::Number {
+                        value: 5,
+                        negative: false,
+                        decimal_places: 2,
+                    }
+                )),
+                // 单个空格作为字面量更短
+                Ok((5, Token::Literal(b" "[..].into()))),
+                Ok((
+                    6,
+                    Token::Number {
+                        value: 7,
+                        negative: true,
+                        decimal_places: 0,
+                    }
+                )),
+                Ok((7, Token::Literal(b"x"[..].into()))),
+            ]
+        );
+
+        // 原样存储
+        assert_eq!(
+            tokens(&[0x48, 0xff, b'A']),
+            [Ok((0, Token::Literal(b"\xffA"[..].into())))]
+        );
+        assert!(tokens(&[]).is_empty());
+
+        // 出错之后结束
+        assert_eq!(
+            tokens(&[0x40, b'A', 0x7f, b'B']),
+            [
+                Ok((0, Token::Literal(b"A"[..].into()))),
+                Err(ZsanError::InvalidToken(1)),
+            ]
+        );
+        assert_eq!(
+            Tokens::new(b"1").unwrap_err(),
+            ZsanError::InvalidHeader(b'1')
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn tokens_write_the_text(
+            input in "[0-9 .\\-A]{0,200}|\\PC{0,50}",
+            checksum in any::<bool>(),
+            entropy in any::<bool>(),
+        ) {
+            let mut compressed = Vec::new();
+            Compressor::builder()
+                .checksum(checksum)
+                .entropy(entropy)
+                .build()
+                .compress(&input, &mut compressed);
+            let mut text = Vec::new();
+            for token in Tokens::new(&compressed).unwrap() {
+                token.unwrap().1.write(&mut text);
+            }
+            prop_assert_eq!(text, input.into_bytes());
+        }
+
+        #[test]
+        fn same_errors_as_decompress(
+            first_byte in 0b_0100_0000..=0b_0100_0011_u8,
+            tokens in prop::collection::vec(any::<u8>(), 0..64),
+        ) {
+            let mut input = vec![first_byte];
+            input.extend_from_slice(&tokens);
+            let mut text = Vec::new();
+            let result = Tokens::new(&input)
+                .unwrap()
+                .try_for_each(|token| token.map(|(_, token)| token.write(&mut text)));
+            let mut expected = Vec::new();
+            prop_assert_eq!(&result, &crate::decompress(&input, &mut expected));
+            if result.is_ok() {
+                prop_assert_eq!(text, expected);
+            }
+        }
+    }
+}
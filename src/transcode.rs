@@ -0,0 +1,275 @@
+//! Re-encode a compressed record with other options.
+
+use std::borrow::Cow;
+
+use crate::zsan::{LITERAL_LIMIT, Record, numerical_decoder};
+use crate::zsan_parser::{
+    Block, NumericalBlock, is_number_byte, is_single_block, retrave_blocks, retrave_blocks_scalar,
+};
+use crate::{Compressor, ZsanError};
+
+impl Compressor {
+    /// Re-encode the compressed record `input` with these options and append the
+    /// result to `out`.
+    ///
+    /// The result is exactly what [`Compressor::compress`] produces for the text of
+    /// `input`, e.g. another encoding mode once the numbers allow it, or the current
+    /// format for records of an older one. Numbers and runs of spaces are taken from
+    /// their tokens, so only runs of literal bytes are parsed again, which is faster
+    /// than decompressing and compressing the text. Only if a token does not cover
+    /// exactly a number or run of spaces of the text, e.g. two adjacent number
+    /// tokens, the whole text is parsed again.
+    ///
+    /// ```
+    /// let mut entropy = Vec::new();
+    /// zsan::compress_entropy("ABC      -12.5", &mut entropy);
+    ///
+    /// let mut plain = Vec::new();
+    /// zsan::Compressor::default().transcode(&entropy, &mut plain).unwrap();
+    /// let mut expected = Vec::new();
+    /// zsan::compress("ABC      -12.5", &mut expected);
+    /// assert_eq!(plain, expected);
+    /// ```
+    ///
+    /// Fails like [`crate::decompress`], including on a checksum mismatch, and then
+    /// appends nothing to `out`.
+    pub fn transcode(&self, input: &[u8], out: &mut Vec<u8>) -> Result<(), ZsanError> {
+        if input.is_empty() {
+            return Ok(());
+        }
+        let record = Record::parse(input)?;
+        let (text, blocks) = if record.header.stored {
+            (record.payload, None)
+        } else {
+            let (text, blocks) = decode_blocks(record.header.first_byte, &record.payload)?;
+            (Cow::Owned(text), Some(blocks))
+        };
+
+        if let Some(expected) = record.checksum {
+            let actual = crate::checksum::crc32c(&text);
+            if actual != expected {
+                return Err(ZsanError::ChecksumMismatch { expected, actual });
+            }
+        }
+        match blocks {
+            Some(blocks) => crate::zsan::compress_parsed(self, &text, || blocks, out),
+            None => crate::zsan::compress_bytes(self, &text, out),
+        }
+        Ok(())
+    }
+}
+
+/// 解码token流, 同时得到 `retrave_blocks` 对原文的结果: 数字和空格token直接成为块,
+/// 只有字面量需要重新解析. token不在块的边界上时, 比如两个相邻的数字token, 重新解析整个原文
+fn decode_blocks(first_byte: u8, stream: &[u8]) -> Result<(Vec<u8>, Vec<Block>), ZsanError> {
+    let numerical_decoder = numerical_decoder(first_byte);
+    let mut text = Vec::with_capacity(stream.len() * 2);
+    let mut blocks = Vec::new();
+    // 由token得到的块
+    let mut from_tokens = Vec::new();
+    // 当前字面量在原文中的起点
+    let mut literal_start = 0;
+    let mut index = 0;
+    while index < stream.len() {
+        let b = stream[index];
+        if b < LITERAL_LIMIT {
+            text.push(b);
+            index += 1;
+            continue;
+        }
+
+        parse_literal(&text, literal_start, &mut blocks);
+        let start = text.len();
+        if crate::all_ascii::is_space(b) {
+            let count = crate::all_ascii::space::space_len(b);
+            text.resize(start + count, b' ');
+            index += 1;
+            // 连续的空格token是同一个块
+            match blocks.last_mut() {
+                Some(Block::Space(run_start, size)) if *run_start + *size == start => {
+                    *size += count
+                }
+                _ => {
+                    from_tokens.push(blocks.len());
+                    blocks.push(Block::Space(start, count));
+                }
+            }
+        } else if let Some((number, len)) = crate::all_ascii::is_numerical(b)
+            .then(|| numerical_decoder(&stream[index..]))
+            .flatten()
+        {
+            number.write(&mut text);
+            index += len;
+            from_tokens.push(blocks.len());
+            blocks.push(Block::Numerical(
+                start,
+                text.len() - start,
+                NumericalBlock {
+                    base: number.value,
+                    negative: number.negative,
+                    decimal_places: number.decimal_places as usize,
+                },
+            ));
+        } else {
+            return Err(ZsanError::InvalidToken(index));
+        }
+        literal_start = text.len();
+    }
+    parse_literal(&text, literal_start, &mut blocks);
+    if !from_tokens
+        .into_iter()
+        .all(|index| on_block_boundaries(&text, &blocks[index]))
+    {
+        blocks = retrave_blocks(&text).2;
+    }
+    Ok((text, blocks))
+}
+
+/// 由token得到的块是否和解析原文得到的相同. 之前和之后的字面量这时也从块的边界开始和结束
+fn on_block_boundaries(text: &[u8], block: &Block) -> bool {
+    match *block {
+        // 数字前后都不能有属于数字的字节, `-` 和 `.` 也不行
+        Block::Numerical(start, size, ref number) => {
+            is_single_block(number.base, number.decimal_places as u8)
+                && (start == 0 || !is_number_byte(text[start - 1]))
+                && text
+                    .get(start + size)
+                    .is_none_or(|&b| !b.is_ascii_digit() && b != b'.')
+        }
+        // 空格串完整, `-` 之后的第一个空格会被解析器跳过
+        Block::Space(start, size) => {
+            size > 0
+                && (start == 0 || !matches!(text[start - 1], b' ' | b'-'))
+                && text.get(start + size) != Some(&b' ')
+        }
+    }
+}
+
+/// 字面量从块的边界开始和结束, 单独解析的结果与解析整个原文相同.
+/// 字面量一般很短, 逐字节解析比先分类快
+fn parse_literal(text: &[u8], start: usize, blocks: &mut Vec<Block>) {
+    let literal = &text[start..];
+    if !literal.iter().any(|&b| b == b' ' || b.is_ascii_digit()) {
+        return;
+    }
+    let (_, _, literal_blocks) = retrave_blocks_scalar(literal);
+    blocks.extend(literal_blocks.into_iter().map(|block| match block {
+        Block::Numerical(offset, size, number) => Block::Numerical(start + offset, size, number),
+        Block::Space(offset, size) => Block::Space(start + offset, size),
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Compressor, ZsanError};
+    use proptest::prelude::*;
+
+    fn compress(compressor: &Compressor, text: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        compressor.compress(text, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_transcode() {
+        // 小数都变成了字面量, 可以换成只有正整数的方式
+        let text = "A 12.5  1234  -7";
+        let source = compress(&Compressor::default(), text);
+        assert_eq!(source[0], 0x43);
+        let integers = Compressor::builder()
+            .decimals(false)
+            .negatives(false)
+            .build();
+        let mut out = b"kept".to_vec();
+        integers.transcode(&source, &mut out).unwrap();
+        assert_eq!(out[4], 0x40);
+        assert_eq!(out[4..], compress(&integers, text));
+
+        let mut out = Vec::new();
+        Compressor::default().transcode(&[], &mut out).unwrap();
+        assert!(out.is_empty());
+
+        let mut corrupted = compress(&Compressor::builder().checksum(true).build(), text);
+        *corrupted.last_mut().unwrap() ^= 1;
+        let mut out = b"kept".to_vec();
+        assert!(matches!(
+            Compressor::default().transcode(&corrupted, &mut out),
+            Err(ZsanError::ChecksumMismatch { .. })
+        ));
+        assert_eq!(
+            Compressor::default().transcode(&[0x40, b'A', 0x7f], &mut out),
+            Err(ZsanError::InvalidToken(1))
+        );
+        assert_eq!(out, b"kept");
+    }
+
+    fn options() -> impl Strategy<Value = Compressor> {
+        (
+            (1..4_usize, 1..4_usize, any::<[bool; 4]>()),
+            any::<[bool; 5]>(),
+        )
+            .prop_map(
+                |(
+                    (min_space_run, min_number_len, [spaces, integers, negatives, decimals]),
+                    [entropy, optimal, checksum, token, ordered],
+                )| {
+                    Compressor::builder()
+                        .min_space_run(min_space_run)
+                        .min_number_len(min_number_len)
+                        .spaces(spaces)
+                        .integers(integers)
+                        .negatives(negatives)
+                        .decimals(decimals)
+                        .tie_break(if token {
+                            crate::TieBreak::Token
+                        } else {
+                            crate::TieBreak::Literal
+                        })
+                        .entropy(entropy)
+                        .optimal_parse(optimal)
+                        .checksum(checksum)
+                        .order_preserving(ordered)
+                        .build()
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn same_as_compressing_the_text(
+            source in options(),
+            target in options(),
+            text in "[0-9 .\\-A]{0,200}|\\PC{0,50}",
+        ) {
+            let mut out = Vec::new();
+            target.transcode(&compress(&source, &text), &mut out).unwrap();
+            prop_assert_eq!(&out, &compress(&target, &text));
+            let mut back = Vec::new();
+            crate::decompress(&out, &mut back).unwrap();
+            prop_assert_eq!(back, text.as_bytes());
+        }
+
+        #[test]
+        fn any_token_stream(
+            mode in 0..4_u8,
+            stream in prop::collection::vec(prop_oneof![0x20..0x7f_u8, 0x80..=0xff_u8], 0..24),
+        ) {
+            // 任意可以解码的token流, 包括编码器不会产生的, 结果都和压缩原文相同
+            let mut input = vec![0x40 | mode];
+            input.extend_from_slice(&stream);
+            let mut text = Vec::new();
+            if crate::decompress(&input, &mut text).is_ok() {
+                let text = String::from_utf8(text).unwrap();
+                let mut out = Vec::new();
+                Compressor::default().transcode(&input, &mut out).unwrap();
+                prop_assert_eq!(out, compress(&Compressor::default(), &text));
+            }
+        }
+
+        #[test]
+        fn any_bytes_fail_like_decompress(input in prop::collection::vec(any::<u8>(), 0..64)) {
+            let result = Compressor::default().transcode(&input, &mut Vec::new());
+            prop_assert_eq!(result.err(), crate::decompress(&input, &mut Vec::new()).err());
+        }
+    }
+}
@@ -8,7 +8,7 @@ macro_rules! vle_encode_loop {
     ($value:ident, $out:ident) => {
         loop {
             let val = ($value & super::VLE_VALIDATE_BITS_MASK as u64) as u8;
-            $value = $value >> super::VLE_VALIDATE_BITS;
+            $value >>= super::VLE_VALIDATE_BITS;
             if $value > 0 {
                 $out.push(val | super::VLE_CONTINUOUS_FLAG);
             } else {
@@ -20,13 +20,20 @@ macro_rules! vle_encode_loop {
 }
 
 // Macro to encapsulate the VLE decoding loop logic
+// returns None from the enclosing function if the input ends early or the value overflows u64
 #[macro_export]
 macro_rules! vle_decode_loop {
     ($input:expr, $result:ident, $bits_cnt:ident, $index:ident) => {
         loop {
-            let byte = $input[$index];
+            let byte = *$input.get($index)?;
             let val = (byte & super::VLE_VALIDATE_BITS_MASK) as u64;
-            $result = $result | (val << $bits_cnt);
+            // 移出u64的位不能丢掉, 例如第10个字节只能是0或1
+            if $bits_cnt >= u64::BITS as u8
+                || ($bits_cnt > 0 && val >> (u64::BITS as u8 - $bits_cnt) != 0)
+            {
+                return None;
+            }
+            $result |= val << $bits_cnt;
             if (byte & super::VLE_CONTINUOUS_FLAG) == 0 {
                 break;
             }
@@ -1,6 +1,6 @@
-/// neightly feature #![feature(macro_metavar_expr_concat)]
-/// 变长编码
-/// 首字节有效位数大于1
+// neightly feature #![feature(macro_metavar_expr_concat)]
+// 变长编码
+// 首字节有效位数大于1
 // macro_rules! vle_n {
 //   ($n_val: literal, $n_name: literal) => {
 //     const ${ concat(VALIDATE_BITS_, $n_name) }:u8 = $n_val - 1_u8;
@@ -24,7 +24,7 @@
 //     /// 变长编码
 //     /// 首字节有效位数大于1
 //     #[inline]
-//     pub fn ${ concat(decode_, $n_name) }(input: &[u8]) -> (u64, usize) {
+//     pub fn ${ concat(decode_, $n_name) }(input: &[u8]) -> Option<(u64, usize)> {
 //       let mut result = (input[0] & ${ concat(VALIDATE_MASK_, $n_name) }) as u64;
 //       if input[0] & ${ concat(CONTINUOUS_FLAG_, $n_name) } == 0 {
 //         return (result, 1);
@@ -50,7 +50,7 @@ pub fn encode_4(input: u64) -> Vec<u8> {
     let mut value = input;
     let mut out = Vec::with_capacity(16);
     let val = (input & VALIDATE_MASK_4 as u64) as u8;
-    value = value >> VALIDATE_BITS_4;
+    value >>= VALIDATE_BITS_4;
     if value > 0 {
         out.push(val | CONTINUOUS_FLAG_4);
         vle_encode_loop!(value, out);
@@ -63,16 +63,17 @@ pub fn encode_4(input: u64) -> Vec<u8> {
 /// 变长编码
 /// 首字节有效位数大于1
 #[inline]
-pub fn decode_4(input: &[u8]) -> (u64, usize) {
-    let mut result = (input[0] & VALIDATE_MASK_4) as u64;
-    if input[0] & CONTINUOUS_FLAG_4 == 0 {
-        return (result, 1);
+pub fn decode_4(input: &[u8]) -> Option<(u64, usize)> {
+    let first = *input.first()?;
+    let mut result = (first & VALIDATE_MASK_4) as u64;
+    if first & CONTINUOUS_FLAG_4 == 0 {
+        return Some((result, 1));
     }
     let mut bits_cnt = VALIDATE_BITS_4;
     let mut index = 1;
 
     vle_decode_loop!(input, result, bits_cnt, index);
-    (result, index + 1)
+    Some((result, index + 1))
 }
 
 const VALIDATE_BITS_5: u8 = 5 - 1_u8;
@@ -83,7 +84,7 @@ pub fn encode_5(input: u64) -> Vec<u8> {
     let mut value = input;
     let mut out = Vec::with_capacity(16);
     let val = (input & VALIDATE_MASK_5 as u64) as u8;
-    value = value >> VALIDATE_BITS_5;
+    value >>= VALIDATE_BITS_5;
     if value > 0 {
         out.push(val | CONTINUOUS_FLAG_5);
         vle_encode_loop!(value, out);
@@ -96,16 +97,17 @@ pub fn encode_5(input: u64) -> Vec<u8> {
 /// 变长编码
 /// 首字节有效位数大于1
 #[inline]
-pub fn decode_5(input: &[u8]) -> (u64, usize) {
-    let mut result = (input[0] & VALIDATE_MASK_5) as u64;
-    if input[0] & CONTINUOUS_FLAG_5 == 0 {
-        return (result, 1);
+pub fn decode_5(input: &[u8]) -> Option<(u64, usize)> {
+    let first = *input.first()?;
+    let mut result = (first & VALIDATE_MASK_5) as u64;
+    if first & CONTINUOUS_FLAG_5 == 0 {
+        return Some((result, 1));
     }
     let mut bits_cnt = VALIDATE_BITS_5;
     let mut index = 1;
 
     vle_decode_loop!(input, result, bits_cnt, index);
-    (result, index + 1)
+    Some((result, index + 1))
 }
 
 const VALIDATE_BITS_6: u8 = 6 - 1_u8;
@@ -116,7 +118,7 @@ pub fn encode_6(input: u64) -> Vec<u8> {
     let mut value = input;
     let mut out = Vec::with_capacity(16);
     let val = (input & VALIDATE_MASK_6 as u64) as u8;
-    value = value >> VALIDATE_BITS_6;
+    value >>= VALIDATE_BITS_6;
     if value > 0 {
         out.push(val | CONTINUOUS_FLAG_6);
         vle_encode_loop!(value, out);
@@ -130,22 +132,26 @@ pub fn encode_6(input: u64) -> Vec<u8> {
 /// 变长编码
 /// 首字节有效位数大于1
 #[inline]
-pub fn decode_6(input: &[u8]) -> (u64, usize) {
-    let mut result = (input[0] & VALIDATE_MASK_6) as u64;
-    if input[0] & CONTINUOUS_FLAG_6 == 0 {
-        return (result, 1);
+pub fn decode_6(input: &[u8]) -> Option<(u64, usize)> {
+    let first = *input.first()?;
+    let mut result = (first & VALIDATE_MASK_6) as u64;
+    if first & CONTINUOUS_FLAG_6 == 0 {
+        return Some((result, 1));
     }
     let mut bits_cnt = VALIDATE_BITS_6;
     let mut index = 1;
 
     vle_decode_loop!(input, result, bits_cnt, index);
-    (result, index + 1)
+    Some((result, index + 1))
 }
 
 #[cfg(test)]
 mod tests {
 
+    use proptest::prelude::*;
+
     #[test]
+    #[allow(clippy::unusual_byte_groupings)]
     fn test_encode() {
         let out = super::encode_6(0b_1_1101010_1001101_01100);
         out.iter().for_each(|i| println!("{i:08b}"));
@@ -159,12 +165,59 @@ mod tests {
     fn test_all() {
         for i in 0..u8::MAX {
             let out = super::encode_5(i as u64);
-            assert_eq!(super::decode_5(out.as_slice()).0, i as u64);
+            assert_eq!(super::decode_5(out.as_slice()).unwrap().0, i as u64);
         }
 
         for i in [u64::MIN, 1258, 999999999, u64::MAX] {
-            let out = super::encode_5(i as u64);
-            assert_eq!(super::decode_5(out.as_slice()).0, i as u64);
+            let out = super::encode_5(i);
+            assert_eq!(super::decode_5(out.as_slice()).unwrap().0, i);
+        }
+    }
+
+    #[test]
+    fn test_overflow() {
+        // u64::MAX 的最后一个字节只用了低位, 其余的位超出u64
+        for (encode, decode) in [
+            (
+                super::encode_4 as fn(u64) -> Vec<u8>,
+                super::decode_4 as fn(&[u8]) -> _,
+            ),
+            (super::encode_5, super::decode_5),
+            (super::encode_6, super::decode_6),
+        ] {
+            let mut out = encode(u64::MAX);
+            assert_eq!(decode(&out), Some((u64::MAX, out.len())));
+            *out.last_mut().unwrap() = 0x7f;
+            assert_eq!(decode(&out), None);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_4(val in any::<u64>(), tail in prop::collection::vec(any::<u8>(), 0..4)) {
+            let mut out = super::encode_4(val);
+            let len = out.len();
+            out.extend_from_slice(&tail);
+            prop_assert_eq!(super::decode_4(&out), Some((val, len)));
+            prop_assert_eq!(super::decode_4(&out[..len - 1]), None);
+        }
+
+        #[test]
+        fn round_trip_5(val in any::<u64>(), tail in prop::collection::vec(any::<u8>(), 0..4)) {
+            let mut out = super::encode_5(val);
+            let len = out.len();
+            out.extend_from_slice(&tail);
+            prop_assert_eq!(super::decode_5(&out), Some((val, len)));
+            prop_assert_eq!(super::decode_5(&out[..len - 1]), None);
+        }
+
+        #[test]
+        fn round_trip_6(val in any::<u64>(), tail in prop::collection::vec(any::<u8>(), 0..4)) {
+            let mut out = super::encode_6(val);
+            let len = out.len();
+            out.extend_from_slice(&tail);
+            prop_assert_eq!(super::decode_6(&out), Some((val, len)));
+            prop_assert_eq!(super::decode_6(&out[..len - 1]), None);
         }
     }
 }
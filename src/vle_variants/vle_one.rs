@@ -5,7 +5,7 @@ pub fn encode_1(input: u64) -> Vec<u8> {
     let mut value = input;
     let mut out = Vec::with_capacity(16);
     let val = (input & 1_u64) as u8;
-    value = value >> 1;
+    value >>= 1;
     out.push(val);
     vle_encode_loop!(value, out);
     out
@@ -14,18 +14,21 @@ pub fn encode_1(input: u64) -> Vec<u8> {
 /// 变长编码
 /// 第一个字节只有1个有效位
 #[inline]
-pub fn decode_1(input: &[u8]) -> (u64, usize) {
+pub fn decode_1(input: &[u8]) -> Option<(u64, usize)> {
     // decode first byte
-    let mut result = (input[0] & 1) as u64;
+    let mut result = (*input.first()? & 1) as u64;
     let mut bits_cnt = 1;
     let mut index: usize = 1;
     vle_decode_loop!(input, result, bits_cnt, index);
-    (result, index + 1)
+    Some((result, index + 1))
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     #[test]
+    #[allow(clippy::unusual_byte_groupings)]
     fn test_encode_1() {
         let out = super::encode_1(0b_1101010_1001101_0);
         assert_eq!(out, vec![0b_0000_0000, 0b_1100_1101, 0b_0110_1010]);
@@ -39,7 +42,18 @@ mod tests {
     fn test_all() {
         for i in 0..u8::MAX {
             let out = super::encode_1(i as u64);
-            assert_eq!(super::decode_1(out.as_slice()).0, i as u64);
+            assert_eq!(super::decode_1(out.as_slice()).unwrap().0, i as u64);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_1(val in any::<u64>(), tail in prop::collection::vec(any::<u8>(), 0..4)) {
+            let mut out = super::encode_1(val);
+            let len = out.len();
+            out.extend_from_slice(&tail);
+            prop_assert_eq!(super::decode_1(&out), Some((val, len)));
+            prop_assert_eq!(super::decode_1(&out[..len - 1]), None);
         }
     }
 }
@@ -9,17 +9,20 @@ pub fn encode_0(input: u64) -> Vec<u8> {
 
 /// 变长编码
 #[inline]
-pub fn decode_0(input: &[u8]) -> (u64, usize) {
+pub fn decode_0(input: &[u8]) -> Option<(u64, usize)> {
     let mut result = 0;
     let mut bits_cnt = 0;
     let mut index: usize = 0;
     vle_decode_loop!(input, result, bits_cnt, index);
-    (result, index + 1)
+    Some((result, index + 1))
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     #[test]
+    #[allow(clippy::unusual_byte_groupings)]
     fn test_encode() {
         let out = super::encode_0(0b_1_1101010_1001101_01100);
         out.iter().for_each(|i| {
@@ -31,9 +34,38 @@ mod tests {
     #[test]
     fn test_all() {
         for i in [u64::MIN, 1258, 999999999, u64::MAX] {
-            let out = super::encode_0(i as u64);
+            let out = super::encode_0(i);
+            let len = out.len();
+            assert_eq!(super::decode_0(out.as_slice()), Some((i, len)));
+        }
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut max = vec![0xff; 9];
+        max.push(0x01);
+        assert_eq!(super::decode_0(&max), Some((u64::MAX, 10)));
+
+        // 第10个字节的高6位超出u64
+        let mut overflow = vec![0xff; 9];
+        overflow.push(0x7f);
+        assert_eq!(super::decode_0(&overflow), None);
+        overflow[9] = 0x02;
+        assert_eq!(super::decode_0(&overflow), None);
+        // 超过10个字节
+        let mut long = vec![0x80; 10];
+        long.push(0x00);
+        assert_eq!(super::decode_0(&long), None);
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_0(val in any::<u64>(), tail in prop::collection::vec(any::<u8>(), 0..4)) {
+            let mut out = super::encode_0(val);
             let len = out.len();
-            assert_eq!(super::decode_0(out.as_slice()), (i as u64, len));
+            out.extend_from_slice(&tail);
+            prop_assert_eq!(super::decode_0(&out), Some((val, len)));
+            prop_assert_eq!(super::decode_0(&out[..len - 1]), None);
         }
     }
 }
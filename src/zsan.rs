@@ -1,33 +1,93 @@
+use std::borrow::Cow;
+
+use crate::all_ascii::Number;
+use crate::header::*;
 use crate::zsan_parser::{Block, NumericalBlock, retrave_blocks};
+use crate::{Compressor, ZsanError};
 
-const ZSAN_FLAG_MASK: u8 = 0b_0100_0000;
+/// 大于等于这个值的字节在token流里有特殊含义, 不能作为字面量
+pub(crate) const LITERAL_LIMIT: u8 = 0b_0111_1111;
 
-/// 00:方式1:所有的Numerical都是正整数
-const FIRST_BYTE_UNSIGNED_INTEGER: u8 = 0b_0000_0000;
-/// 01:方式2:如果所有的Numerical都是整数但是包含负数
-const FIRST_BYTE_INTEGER: u8 = 0b_0000_0010;
+/// Compress `src` and append the result to `out`, using the default [`Compressor`].
+///
+/// The output is never more than one byte longer than `src`: whenever the token
+/// stream is not smaller than the input, the input is stored as is.
+pub fn compress(src: &str, out: &mut Vec<u8>) {
+    compress_with(&Compressor::default(), src, out);
+}
 
-/// 10:方式3:如果所有的Numerical都是正数但是包含小数
-const FIRST_BYTE_UNSIGNED_DECIMAL: u8 = 0b_0000_0001;
-/// 11:方式4:所有的Numerical包含负数和小数
-const FIRST_BYTE_DECIMAL: u8 = 0b_0000_0011;
+/// Same as [`compress`], but the token stream is additionally Huffman coded.
+///
+/// The code table is stored in front of the payload, so this only pays off for
+/// inputs of a few hundred bytes or more, e.g. many records joined by `\n`.
+pub fn compress_entropy(src: &str, out: &mut Vec<u8>) {
+    compress_with(&Compressor::builder().entropy(true).build(), src, out);
+}
 
-const ENCODE_MODE_MASK: u8 = 0b_0000_0011;
+pub(crate) fn compress_with(options: &Compressor, src: &str, out: &mut Vec<u8>) {
+    compress_bytes(options, src.as_bytes(), out);
+}
 
-const NEGATIVE_FLAG: u8 = 0b_0000_0010;
-const DECIMAL_FLAG: u8 = 0b_0000_0001;
+/// 任意字节也可以压缩, 包含不能作为字面量的字节时原样存储
+pub(crate) fn compress_bytes(options: &Compressor, src: &[u8], out: &mut Vec<u8>) {
+    compress_parsed(options, src, || retrave_blocks(src).2, out);
+}
 
-pub fn compress(src: &str, out: &mut Vec<u8>) {
+/// 同 [`compress_bytes`], `blocks` 返回 `retrave_blocks(src)` 的结果, 只在需要时调用
+pub(crate) fn compress_parsed(
+    options: &Compressor,
+    src: &[u8],
+    blocks: impl FnOnce() -> Vec<Block>,
+    out: &mut Vec<u8>,
+) {
     if src.is_empty() {
         return;
     }
-    let src = src.as_bytes();
-    let (has_negative, has_decimal, blocks) = retrave_blocks(src);
+    if options.order_preserving {
+        crate::ordered::compress(src, out);
+        return;
+    }
+    let start = out.len();
+    compress_body(src, blocks, options, start, out);
 
-    let first_byte =
-        if has_negative { NEGATIVE_FLAG } else { 0 } | if has_decimal { DECIMAL_FLAG } else { 0 };
+    if options.checksum {
+        out[start] |= EXTENSION_FLAG;
+        out.insert(start + 1, CHECKSUM_FEATURE as u8);
+        out.extend_from_slice(&crate::checksum::crc32c(src).to_le_bytes());
+    }
+}
 
-    let numerical_compressor = match first_byte {
+/// 首字节和之后的token流, 必要时原样存储
+fn compress_body(
+    src: &[u8],
+    blocks: impl FnOnce() -> Vec<Block>,
+    options: &Compressor,
+    start: usize,
+    out: &mut Vec<u8>,
+) {
+    if src.iter().all(|&b| b < LITERAL_LIMIT) {
+        if options.optimal_parse {
+            crate::optimal::compress_tokens(src, options, out);
+        } else {
+            compress_tokens(src, blocks(), options, out);
+        }
+        if options.entropy {
+            entropy_code(start, out);
+        }
+        if out.len() - start <= src.len() {
+            return;
+        }
+        out.truncate(start);
+    }
+
+    out.push(STORED_FLAG | ZSAN_FLAG_MASK);
+    out.extend_from_slice(src);
+}
+
+pub(crate) type NumericalCompressor = fn(u64, bool, u8, &mut Vec<u8>) -> bool;
+
+pub(crate) fn numerical_compressor(first_byte: u8) -> NumericalCompressor {
+    match first_byte & ENCODE_MODE_MASK {
         FIRST_BYTE_UNSIGNED_INTEGER => {
             crate::all_ascii::unsigned_integer::compress_unsigned_integer
         }
@@ -37,22 +97,35 @@ pub fn compress(src: &str, out: &mut Vec<u8>) {
         }
         FIRST_BYTE_DECIMAL => crate::all_ascii::decimal::compress_decimal,
         _ => panic!("invalid first byte"),
-    };
+    }
+}
+
+fn compress_tokens(src: &[u8], blocks: Vec<Block>, options: &Compressor, out: &mut Vec<u8>) {
+    let first_byte = greedy_mode(&blocks, options);
+    let numerical_compressor = numerical_compressor(first_byte);
 
     out.push(first_byte | ZSAN_FLAG_MASK);
     let mut processed_len = 0;
     blocks.into_iter().for_each(|block| {
-        match block {
-            Block::Space(start, size) => {
-                if start > processed_len {
-                    out.extend_from_slice(&src[processed_len..start]);
-                    processed_len += start - processed_len;
+        let (start, size) = match block {
+            Block::Space(start, size) => (start, size),
+            Block::Numerical(start, size, _) => (start, size),
+        };
+        if start > processed_len {
+            out.extend_from_slice(&src[processed_len..start]);
+            processed_len += start - processed_len;
+        }
+
+        let mark = out.len();
+        let encoded = match block {
+            Block::Space(_, size) => {
+                options.accepts_space(size) && {
+                    crate::all_ascii::space::compress_space(size, out);
+                    true
                 }
-                crate::all_ascii::space::compress_space(size, out);
-                processed_len += size;
             }
             Block::Numerical(
-                start,
+                _,
                 size,
                 NumericalBlock {
                     base,
@@ -60,17 +133,16 @@ pub fn compress(src: &str, out: &mut Vec<u8>) {
                     decimal_places,
                 },
             ) => {
-                if start > processed_len {
-                    out.extend_from_slice(&src[processed_len..start]);
-                    processed_len += start - processed_len;
-                }
-                if !numerical_compressor(base, negative, decimal_places as u8, out) {
-                    out.extend_from_slice(&src[processed_len..processed_len + size]);
-                }
-
-                processed_len += size;
+                options.accepts_number(size, negative, decimal_places)
+                    && numerical_compressor(base, negative, decimal_places as u8, out)
             }
         };
+        if !encoded || !options.prefers_token(out.len() - mark, size) {
+            out.truncate(mark);
+            out.extend_from_slice(&src[processed_len..processed_len + size]);
+        }
+
+        processed_len += size;
     });
 
     if processed_len < src.len() {
@@ -78,11 +150,242 @@ pub fn compress(src: &str, out: &mut Vec<u8>) {
     }
 }
 
-pub fn decompress(input: &[u8], out: &mut Vec<u8>) {
+/// 能表示所有会被编码成token的数字的最简单的编码方式
+fn greedy_mode(blocks: &[Block], options: &Compressor) -> u8 {
+    let (has_negative, has_decimal) = blocks.iter().fold(
+        (false, false),
+        |(has_negative, has_decimal), block| match block {
+            Block::Numerical(_, size, number)
+                if options.accepts_number(*size, number.negative, number.decimal_places) =>
+            {
+                (
+                    has_negative || number.negative,
+                    has_decimal || number.decimal_places > 0,
+                )
+            }
+            _ => (has_negative, has_decimal),
+        },
+    );
+
+    (if has_negative { NEGATIVE_FLAG } else { 0 }) | if has_decimal { DECIMAL_FLAG } else { 0 }
+}
+
+/// 对首字节之后的token流做哈夫曼编码, 没有变小时保持原样
+fn entropy_code(start: usize, out: &mut Vec<u8>) {
+    let tokens = out.split_off(start);
+    out.push(tokens[0] | ENTROPY_FLAG);
+    crate::entropy::encode(&tokens[1..], out);
+    if out.len() - start >= tokens.len() {
+        out.truncate(start);
+        out.extend_from_slice(&tokens);
+    }
+}
+
+/// Decompress `input` and append the text to `out`.
+///
+/// Fails if the header is not understood by this version, see [`FORMAT_VERSION`],
+/// if a token is malformed, or if the record carries a checksum that does not
+/// match the decoded text. Nothing is appended to `out` when an error is returned.
+///
+/// `out` grows at most once, by exactly the length of the text, see
+/// [`decompressed_len`].
+pub fn decompress(input: &[u8], out: &mut Vec<u8>) -> Result<(), ZsanError> {
     if input.is_empty() {
-        return;
+        return Ok(());
+    }
+    let text_start = out.len();
+    let result = decompress_record(input, out);
+    if result.is_err() {
+        out.truncate(text_start);
+    }
+    result
+}
+
+/// Length of the text [`decompress`] produces for `input`, without decoding it.
+///
+/// Fails like [`decompress`] for malformed input. The checksum is not verified,
+/// as that needs the text.
+///
+/// ```
+/// let mut compressed = Vec::new();
+/// zsan::compress("6224      ABC20200902       -1312.25", &mut compressed);
+/// assert_eq!(zsan::decompressed_len(&compressed), Ok(36));
+/// ```
+pub fn decompressed_len(input: &[u8]) -> Result<usize, ZsanError> {
+    if input.is_empty() {
+        return Ok(0);
+    }
+    let record = Record::parse(input)?;
+    if record.header.stored {
+        Ok(record.payload.len())
+    } else {
+        tokens_text_len(record.header.first_byte, &record.payload)
+    }
+}
+
+/// Rewrite a record produced with any [`Compressor`] options in the canonical form
+/// and append it to `out`.
+///
+/// The canonical form of a text is what [`compress`] produces for it. It is
+/// deterministic: within a format version, see [`FORMAT_VERSION`], the same text
+/// always gives the same bytes, and different texts give different bytes. So two
+/// canonical records are equal exactly when their texts are equal, and can be
+/// compared and hashed without decompressing them.
+///
+/// ```
+/// let mut plain = Vec::new();
+/// zsan::compress("6224      ABC20200902", &mut plain);
+/// let mut entropy = Vec::new();
+/// zsan::Compressor::builder()
+///     .entropy(true)
+///     .checksum(true)
+///     .build()
+///     .compress("6224      ABC20200902", &mut entropy);
+/// assert_ne!(plain, entropy);
+///
+/// let mut canonical = Vec::new();
+/// zsan::canonicalize(&entropy, &mut canonical).unwrap();
+/// assert_eq!(canonical, plain);
+/// ```
+///
+/// Fails like [`decompress`], including on a checksum mismatch, and then appends
+/// nothing to `out`.
+pub fn canonicalize(input: &[u8], out: &mut Vec<u8>) -> Result<(), ZsanError> {
+    Compressor::default().transcode(input, out)
+}
+
+/// 解析过头部的记录
+pub(crate) struct Record<'a> {
+    pub header: Header,
+    /// 原样存储的原文, 或者token流 (已经做过哈夫曼解码)
+    pub payload: Cow<'a, [u8]>,
+    /// 末尾的校验和
+    pub checksum: Option<u32>,
+}
+
+impl<'a> Record<'a> {
+    pub fn parse(input: &'a [u8]) -> Result<Self, ZsanError> {
+        let header = Header::parse(input)?;
+        let mut payload = &input[header.len..];
+        let mut checksum = None;
+        if header.features & CHECKSUM_FEATURE != 0 {
+            let Some(split) = payload.len().checked_sub(CHECKSUM_LEN) else {
+                return Err(ZsanError::Truncated);
+            };
+            let (body, stored) = payload.split_at(split);
+            payload = body;
+            checksum = Some(u32::from_le_bytes(stored.try_into().unwrap()));
+        }
+
+        // 保序编码的记录先还原成原文, 之后和原样存储的记录一样处理
+        if header.features & ORDERED_FEATURE != 0 {
+            let mut text = Vec::with_capacity(payload.len() * 2);
+            crate::ordered::decompress(payload, &mut text)?;
+            return Ok(Record {
+                header: Header {
+                    stored: true,
+                    ..header
+                },
+                payload: Cow::Owned(text),
+                checksum,
+            });
+        }
+
+        let payload = if header.entropy {
+            let mut tokens = Vec::with_capacity(payload.len() * 2);
+            if !crate::entropy::decode(payload, &mut tokens) {
+                return Err(ZsanError::InvalidEntropyStream);
+            }
+            Cow::Owned(tokens)
+        } else {
+            Cow::Borrowed(payload)
+        };
+        Ok(Record {
+            header,
+            payload,
+            checksum,
+        })
+    }
+}
+
+fn decompress_record(input: &[u8], out: &mut Vec<u8>) -> Result<(), ZsanError> {
+    let record = Record::parse(input)?;
+
+    let text_start = out.len();
+    if record.header.stored {
+        out.extend_from_slice(&record.payload);
+    } else {
+        let first_byte = record.header.first_byte;
+        out.reserve(tokens_text_len(first_byte, &record.payload)?);
+        decompress_tokens(first_byte, &record.payload, out)?;
+    }
+
+    if let Some(expected) = record.checksum {
+        let actual = crate::checksum::crc32c(&out[text_start..]);
+        if actual != expected {
+            return Err(ZsanError::ChecksumMismatch { expected, actual });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) type NumericalDecoder = fn(&[u8]) -> Option<(Number, usize)>;
+
+pub(crate) fn numerical_decoder(first_byte: u8) -> NumericalDecoder {
+    match first_byte & ENCODE_MODE_MASK {
+        FIRST_BYTE_UNSIGNED_INTEGER => crate::all_ascii::unsigned_integer::decode_unsigned_integer,
+        FIRST_BYTE_INTEGER => crate::all_ascii::integer::decode_integer,
+        FIRST_BYTE_UNSIGNED_DECIMAL => crate::all_ascii::unsigned_decimal::decode_unsigned_decimal,
+        FIRST_BYTE_DECIMAL => crate::all_ascii::decimal::decode_decimal,
+        _ => panic!("invalid first byte"),
+    }
+}
+
+/// token流解码后的长度, 只计算长度不写出文本
+fn tokens_text_len(first_byte: u8, input: &[u8]) -> Result<usize, ZsanError> {
+    let numerical_decoder = numerical_decoder(first_byte);
+
+    let mut len = 0;
+    let mut index = 0;
+    while index < input.len() {
+        let b = input[index];
+        if b < LITERAL_LIMIT {
+            len += 1;
+            index += 1;
+        } else if crate::all_ascii::is_space(b) {
+            len += crate::all_ascii::space::space_len(b);
+            index += 1;
+        } else if crate::all_ascii::is_numerical(b) {
+            let (number, token_len) =
+                numerical_decoder(&input[index..]).ok_or(ZsanError::InvalidToken(index))?;
+            len += number.text_len();
+            index += token_len;
+        } else {
+            return Err(ZsanError::InvalidToken(index));
+        }
     }
-    let first_byte = input[0];
+    Ok(len)
+}
+
+/// 解码首字节之后的token流
+fn decompress_tokens(first_byte: u8, input: &[u8], out: &mut Vec<u8>) -> Result<(), ZsanError> {
+    decompress_token_prefix(first_byte, input, 0, true, out).map(|_| ())
+}
+
+/// 数字token最多占用的字节数: 小数的首字节加上最长10个字节的varint
+pub(crate) const MAX_TOKEN_LEN: usize = 11;
+
+/// 解码 `input` 中完整的token, 返回消耗的字节数。
+///
+/// `last` 为 false 时, 末尾不完整的数字token不算错误, 留给下一次调用;
+/// `offset` 是 `input` 在token流中的位置, 用于 [`ZsanError::InvalidToken`]。
+pub(crate) fn decompress_token_prefix(
+    first_byte: u8,
+    input: &[u8],
+    offset: usize,
+    last: bool,
+    out: &mut Vec<u8>,
+) -> Result<usize, ZsanError> {
     let numerical_decompressor = match first_byte & ENCODE_MODE_MASK {
         FIRST_BYTE_UNSIGNED_INTEGER => {
             crate::all_ascii::unsigned_integer::decompress_unsigned_integer
@@ -95,23 +398,349 @@ pub fn decompress(input: &[u8], out: &mut Vec<u8>) {
         _ => panic!("invalid first byte"),
     };
 
-    let mut index = 1;
+    let mut index = 0;
     while index < input.len() {
         let &b = unsafe { input.get_unchecked(index) };
-        if b < 0b_0111_1111 {
+        if b < LITERAL_LIMIT {
             out.push(b);
             index += 1;
         } else if crate::all_ascii::is_space(b) {
             let _ = crate::all_ascii::space::decompress_space(b, out);
             index += 1;
         } else if crate::all_ascii::is_numerical(b) {
-            index += numerical_decompressor(&input[index..], out);
+            match numerical_decompressor(&input[index..], out) {
+                Some(len) => index += len,
+                None if !last && input.len() - index < MAX_TOKEN_LEN => break,
+                None => return Err(ZsanError::InvalidToken(offset + index)),
+            }
+        } else {
+            return Err(ZsanError::InvalidToken(offset + index));
         }
     }
+    Ok(index)
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
+    fn round_trip(input: &str, compress: fn(&str, &mut Vec<u8>)) -> Vec<u8> {
+        let mut out = Vec::new();
+        compress(input, &mut out);
+        let mut final_out = vec![];
+        super::decompress(&out, &mut final_out).unwrap();
+        assert_eq!(String::from_utf8(final_out).unwrap(), input);
+        out
+    }
+
+    proptest! {
+        #[test]
+        fn expands_at_most_one_byte(input in "\\PC*") {
+            prop_assert!(round_trip(&input, super::compress).len() <= input.len() + 1);
+            prop_assert!(round_trip(&input, super::compress_entropy).len() <= input.len() + 1);
+        }
+
+        #[test]
+        fn expands_at_most_one_byte_numerical(input in "[0-9 .\\-A]{0,200}") {
+            prop_assert!(round_trip(&input, super::compress).len() <= input.len() + 1);
+            prop_assert!(round_trip(&input, super::compress_entropy).len() <= input.len() + 1);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn optimal_parse_is_never_larger(input in "[0-9 .\\-A]{0,200}") {
+            let optimal = super::Compressor::builder().optimal_parse(true).build();
+            let mut out = Vec::new();
+            optimal.compress(&input, &mut out);
+            let mut final_out = vec![];
+            super::decompress(&out, &mut final_out).unwrap();
+            prop_assert_eq!(String::from_utf8(final_out).unwrap(), input.clone());
+            prop_assert!(out.len() <= round_trip(&input, super::compress).len());
+        }
+    }
+
+    fn compressor() -> impl Strategy<Value = super::Compressor> {
+        (
+            (1..4_usize, 1..4_usize, any::<[bool; 4]>(), 0..=15_u8),
+            (any::<bool>(), any::<[bool; 3]>()),
+        )
+            .prop_map(
+                |(
+                    (
+                        min_space_run,
+                        min_number_len,
+                        [spaces, integers, negatives, decimals],
+                        places,
+                    ),
+                    (token, [entropy, optimal, checksum]),
+                )| {
+                    super::Compressor::builder()
+                        .min_space_run(min_space_run)
+                        .min_number_len(min_number_len)
+                        .spaces(spaces)
+                        .integers(integers)
+                        .negatives(negatives)
+                        .decimals(decimals)
+                        .max_decimal_places(places)
+                        .tie_break(if token {
+                            crate::TieBreak::Token
+                        } else {
+                            crate::TieBreak::Literal
+                        })
+                        .entropy(entropy)
+                        .optimal_parse(optimal)
+                        .checksum(checksum)
+                        .build()
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn any_options_round_trip(compressor in compressor(), input in "[0-9 .\\-A]{0,200}|\\PC{0,50}") {
+            let mut out = Vec::new();
+            compressor.compress(&input, &mut out);
+            prop_assert_eq!(super::decompressed_len(&out), Ok(input.len()));
+            let mut final_out = vec![];
+            super::decompress(&out, &mut final_out).unwrap();
+            prop_assert_eq!(String::from_utf8(final_out).unwrap(), input);
+        }
+
+        #[test]
+        fn canonical_form_of_any_options(
+            compressor in compressor(),
+            ordered in any::<bool>(),
+            input in "[0-9 .\\-A]{0,200}|\\PC{0,50}",
+        ) {
+            let compressor = if ordered {
+                super::Compressor::builder().order_preserving(true).build()
+            } else {
+                compressor
+            };
+            let mut out = Vec::new();
+            compressor.compress(&input, &mut out);
+            let mut canonical = Vec::new();
+            super::canonicalize(&out, &mut canonical).unwrap();
+            prop_assert_eq!(&canonical, &round_trip(&input, super::compress));
+
+            let mut again = Vec::new();
+            super::canonicalize(&canonical, &mut again).unwrap();
+            prop_assert_eq!(again, canonical);
+        }
+
+        #[test]
+        fn any_bytes_never_panic(input in prop::collection::vec(any::<u8>(), 0..64)) {
+            let mut out = b"kept".to_vec();
+            if super::decompress(&input, &mut out).is_err() {
+                prop_assert_eq!(out, b"kept");
+            }
+        }
+
+        #[test]
+        fn any_token_stream_never_panics(
+            first_byte in 0b_0100_0000..=0b_0100_0111_u8,
+            tokens in prop::collection::vec(any::<u8>(), 0..64),
+        ) {
+            let mut input = vec![first_byte];
+            input.extend_from_slice(&tokens);
+            let mut out = Vec::new();
+            let expected = super::decompress(&input, &mut out).map(|_| out.len());
+            prop_assert_eq!(super::decompressed_len(&input), expected);
+        }
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        use super::ZsanError;
+        let decompress = |input: &[u8]| super::decompress(input, &mut Vec::new());
+
+        // 0x7F既不是字面量也不是token
+        assert_eq!(
+            decompress(&[0x40, b'A', 0x7f]),
+            Err(ZsanError::InvalidToken(1))
+        );
+        // varint在输入末尾中断
+        assert_eq!(
+            decompress(&[0x40, 0b_1110_0000]),
+            Err(ZsanError::InvalidToken(0))
+        );
+        // varint超过64位
+        let mut overlong = vec![0x40, 0b_1110_0000];
+        overlong.extend_from_slice(&[0b_1000_0000; 10]);
+        overlong.push(1);
+        assert_eq!(decompress(&overlong), Err(ZsanError::InvalidToken(0)));
+        // 伪造的超大长度不能导致内存分配失败
+        assert_eq!(
+            decompress(&[0x44, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 0, b'A', 0x10, 0]),
+            Err(ZsanError::InvalidEntropyStream)
+        );
+    }
+
+    #[test]
+    fn test_u64_max() {
+        let mut input = vec![super::FIRST_BYTE_UNSIGNED_INTEGER | super::ZSAN_FLAG_MASK];
+        let mut token = crate::vle_variants::encode_6(u64::MAX);
+        token[0] |= 0b_1100_0000;
+        input.extend_from_slice(&token);
+        let mut out = Vec::new();
+        super::decompress(&input, &mut out).unwrap();
+        assert_eq!(out, u64::MAX.to_string().into_bytes());
+
+        // 20位数字和15位小数
+        let mut input = vec![
+            super::FIRST_BYTE_DECIMAL | super::ZSAN_FLAG_MASK,
+            0b_1101_1111,
+        ];
+        input.extend_from_slice(&crate::vle_variants::encode_0(u64::MAX));
+        let mut out = Vec::new();
+        super::decompress(&input, &mut out).unwrap();
+        assert_eq!(out, b"18446.744073709551615");
+        assert_eq!(super::decompressed_len(&input), Ok(out.len()));
+    }
+
+    #[test]
+    fn test_decompressed_len() {
+        use super::ZsanError;
+        assert_eq!(super::decompressed_len(&[]), Ok(0));
+        // 原样存储
+        assert_eq!(super::decompressed_len(&[0x48, 0xff, 0xfe]), Ok(2));
+        assert_eq!(
+            super::decompressed_len(&[0x40, b'A', 0x7f]),
+            Err(ZsanError::InvalidToken(1))
+        );
+
+        // 只计算长度, 不检查校验和
+        let mut out = Vec::new();
+        super::Compressor::builder()
+            .checksum(true)
+            .build()
+            .compress("A   123  B", &mut out);
+        *out.last_mut().unwrap() ^= 1;
+        assert!(super::decompress(&out, &mut vec![]).is_err());
+        assert_eq!(super::decompressed_len(&out), Ok(10));
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        use super::ZsanError;
+        let mut out = b"kept".to_vec();
+        super::canonicalize(&[], &mut out).unwrap();
+        // 原样存储的任意字节仍然原样存储
+        super::canonicalize(&[0x48, 0xff, 0xfe], &mut out).unwrap();
+        assert_eq!(out, b"kept\x48\xff\xfe");
+
+        // 手工构造的token不在解析器的块边界上: 两个相邻的数字token "12" 和 "13"
+        let mut out = Vec::new();
+        super::canonicalize(&[0x40, 0xcc, 0xcd], &mut out).unwrap();
+        assert_eq!(out, round_trip("1213", super::compress));
+        assert_eq!(out, [0x40, 0xfd, 0x25]);
+        // 空格token前面是 `-` 或者空格, 数字token后面是 `.`
+        for input in [
+            &[0x40, b'-', 0x82, b'5'][..],
+            &[0x40, b' ', 0x82, b'5'],
+            &[0x40, 0xc5, b'.', b'5'],
+            &[0x40, b'A', 0x80, b'B'],
+        ] {
+            let mut text = Vec::new();
+            super::decompress(input, &mut text).unwrap();
+            let mut out = Vec::new();
+            super::canonicalize(input, &mut out).unwrap();
+            assert_eq!(
+                out,
+                round_trip(std::str::from_utf8(&text).unwrap(), super::compress)
+            );
+        }
+
+        let mut corrupted = Vec::new();
+        super::Compressor::builder()
+            .checksum(true)
+            .build()
+            .compress("A   123  B", &mut corrupted);
+        *corrupted.last_mut().unwrap() ^= 1;
+        let mut out = b"kept".to_vec();
+        assert!(matches!(
+            super::canonicalize(&corrupted, &mut out),
+            Err(ZsanError::ChecksumMismatch { .. })
+        ));
+        assert_eq!(out, b"kept");
+    }
+
+    #[test]
+    fn test_optimal_parse() {
+        let optimal = super::Compressor::builder().optimal_parse(true).build();
+        for (input, greedy_len, optimal_len) in [
+            // 有小数时两位数编码后不比字面量短, 放弃小数反而更短
+            ("0.5 12 13 14 15 16", 18, 14),
+            // 超过18位的数字也是一个token
+            ("12345678901234567890", 12, 11),
+            // 正整数的方式里负数留作字面量
+            ("20 21 22 23 -1", 14, 11),
+        ] {
+            let greedy = round_trip(input, super::compress);
+            assert_eq!(greedy.len(), greedy_len, "{input:?}");
+            let mut out = Vec::new();
+            optimal.compress(input, &mut out);
+            assert_eq!(out.len(), optimal_len, "{input:?}");
+            let mut final_out = vec![];
+            super::decompress(&out, &mut final_out).unwrap();
+            assert_eq!(final_out, input.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        let checksum = super::Compressor::builder().checksum(true).build();
+        for input in ["A   -1.23  B0.1-234", "ABC", "价格 123"] {
+            let mut out = Vec::new();
+            checksum.compress(input, &mut out);
+            let mut plain = Vec::new();
+            super::compress(input, &mut plain);
+            assert_eq!(out.len(), plain.len() + 5);
+
+            let mut final_out = vec![];
+            super::decompress(&out, &mut final_out).unwrap();
+            assert_eq!(String::from_utf8(final_out).unwrap(), input);
+
+            // 任意一个比特翻转都能被发现
+            for i in 2..out.len() {
+                for bit in 0..8 {
+                    let mut corrupted = out.clone();
+                    corrupted[i] ^= 1 << bit;
+                    let mut final_out = vec![];
+                    let result = super::decompress(&corrupted, &mut final_out);
+                    if result.is_ok() {
+                        assert_eq!(String::from_utf8(final_out).unwrap(), input);
+                    } else {
+                        assert!(final_out.is_empty());
+                    }
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        checksum.compress("A   123  B", &mut out);
+        out[3] ^= 1;
+        assert!(matches!(
+            super::decompress(&out, &mut vec![]),
+            Err(super::ZsanError::ChecksumMismatch { .. })
+        ));
+        assert_eq!(
+            super::decompress(&out[..4], &mut vec![]),
+            Err(super::ZsanError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_stored() {
+        for input in ["A", "ABCDEFG", "32 99", "价格 123", "A\u{7f}  B"] {
+            let out = round_trip(input, super::compress);
+            assert_eq!(out[0], super::STORED_FLAG | super::ZSAN_FLAG_MASK);
+            assert_eq!(out.len(), input.len() + 1);
+        }
+        let out = round_trip("A   123456  B", super::compress);
+        assert_eq!(out[0] & super::STORED_FLAG, 0);
+    }
 
     #[test]
     fn test_compress() {
@@ -123,7 +752,7 @@ mod tests {
         }
 
         let mut final_out = vec![];
-        super::decompress(&out, &mut final_out);
+        super::decompress(&out, &mut final_out).unwrap();
         for o in final_out.iter() {
             println!("out\t{:08b}", o);
         }
@@ -141,7 +770,7 @@ mod tests {
         }
 
         let mut final_out = vec![];
-        super::decompress(&out, &mut final_out);
+        super::decompress(&out, &mut final_out).unwrap();
 
         assert_eq!(String::from_utf8(final_out).unwrap(), input);
     }
@@ -156,7 +785,7 @@ mod tests {
         }
 
         let mut final_out = vec![];
-        super::decompress(&out, &mut final_out);
+        super::decompress(&out, &mut final_out).unwrap();
 
         assert_eq!(String::from_utf8(final_out).unwrap(), input);
     }
@@ -170,7 +799,7 @@ mod tests {
             println!("{:08b}", o);
         }
         let mut final_out = vec![];
-        super::decompress(&out, &mut final_out);
+        super::decompress(&out, &mut final_out).unwrap();
         assert_eq!(String::from_utf8(final_out).unwrap(), input);
     }
 
@@ -183,7 +812,7 @@ mod tests {
             println!("{:08b}", o);
         }
         let mut final_out = vec![];
-        super::decompress(&out, &mut final_out);
+        super::decompress(&out, &mut final_out).unwrap();
         assert_eq!(String::from_utf8(final_out).unwrap(), input);
     }
 
@@ -196,7 +825,37 @@ mod tests {
             println!("{:08b}", o);
         }
         let mut final_out = vec![];
-        super::decompress(&out, &mut final_out);
+        super::decompress(&out, &mut final_out).unwrap();
+        assert_eq!(String::from_utf8(final_out).unwrap(), input);
+    }
+
+    #[test]
+    fn test_entropy_compression() {
+        let input = (0..20)
+            .map(|i| {
+                format!(
+                    "{:<10}EFG0990 {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10.1} {:.4}",
+                    9951 + i,
+                    132230 + i * 7,
+                    -13228 - i,
+                    102230,
+                    192230 + i,
+                    4 + i,
+                    46,
+                    5938.6 + i as f64,
+                    561969.6111,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut plain = Vec::new();
+        super::compress(&input, &mut plain);
+        let mut out = Vec::new();
+        super::compress_entropy(&input, &mut out);
+        assert!(out.len() < plain.len());
+
+        let mut final_out = vec![];
+        super::decompress(&out, &mut final_out).unwrap();
         assert_eq!(String::from_utf8(final_out).unwrap(), input);
     }
 
@@ -211,7 +870,7 @@ mod tests {
         }
 
         let mut final_out = vec![];
-        super::decompress(&out, &mut final_out);
+        super::decompress(&out, &mut final_out).unwrap();
         assert_eq!(String::from_utf8(final_out).unwrap(), input);
     }
 }
@@ -1,3 +1,5 @@
+mod scan;
+
 #[derive(Debug, PartialEq)]
 pub(crate) struct NumericalBlock {
     pub base: u64,
@@ -11,15 +13,111 @@ pub(crate) enum Block {
     Space(usize, usize),
 }
 
-const MAX_DECIMAL_PLACES: usize = 0b_0000_1111;
+pub(crate) const MAX_DECIMAL_PLACES: usize = 0b_0000_1111;
+
+/// 不以0开头的数字最多累加18位
+const MAX_DIGITS: usize = 18;
 
+/// 可以属于数字块的字节. 解析器不会回看, 在其他字节之后从头开始解析
+pub(crate) fn is_number_byte(b: u8) -> bool {
+    b.is_ascii_digit() || matches!(b, b'-' | b'.')
+}
+
+/// 这个数字的文本前后都是不属于数字的字节时, 解析出的是不是恰好一个同样的块.
+/// 超过 [`MAX_DIGITS`] 位的数字会被拆开, 没有小数的0不会成为块
+pub(crate) fn is_single_block(value: u64, decimal_places: u8) -> bool {
+    (value > 0 || decimal_places > 0) && value < 10_u64.pow(MAX_DIGITS as u32)
+}
+
+/// 先用SIMD把空格和数字分类成位图, 再按位图找空格块和数字串,
+/// 结果与 [`retrave_blocks_scalar`] 完全相同。
 pub fn retrave_blocks(src: &[u8]) -> (bool, bool, Vec<Block>) {
     let mut has_negative = false;
     let mut has_decimal = false;
 
     let s = src;
     let n = s.len();
-    let mut out = Vec::new();
+    let classes = scan::classify(s);
+    let mut out = Vec::with_capacity(n / 4);
+    let mut idx = 0;
+
+    while idx < n {
+        // 空格
+        let space_end = classes.space_end(idx);
+        if space_end > idx {
+            out.push(Block::Space(idx, space_end - idx));
+            idx = space_end;
+            if idx >= n {
+                break;
+            }
+        }
+
+        let start = idx;
+        let neg = s[idx] == b'-';
+        idx += neg as usize;
+
+        // ---------- 整数 ----------
+        let (mut base, integer_end, significant) = if idx < n && s[idx] == b'0' {
+            (0, idx + 1, 0)
+        } else {
+            // 整数不以0开头, 超过18位的部分留给下一个块
+            let integer_end = classes.digit_end(idx).min(idx + MAX_DIGITS);
+            if integer_end == idx {
+                idx += 1;
+                continue;
+            } // 无整数
+            (
+                scan::parse_digits(0, &s[idx..integer_end]),
+                integer_end,
+                integer_end - idx,
+            )
+        };
+        idx = integer_end;
+
+        // ---------- 小数 ----------
+        let mut dec_pl = 0;
+        if idx < n && s[idx] == b'.' {
+            idx += 1;
+            dec_pl = (classes.digit_end(idx) - idx)
+                .min(MAX_DECIMAL_PLACES)
+                .min(MAX_DIGITS - significant);
+            base = scan::parse_digits(base, &s[idx..idx + dec_pl]);
+            idx += dec_pl;
+        }
+
+        // ---------- 推结果 ----------
+        if base != 0 || dec_pl != 0 {
+            if neg {
+                has_negative = true;
+            }
+            out.push(Block::Numerical(
+                start,
+                if dec_pl == 0 {
+                    integer_end - start
+                } else {
+                    has_decimal = true;
+                    integer_end - start + dec_pl + 1
+                },
+                NumericalBlock {
+                    base,
+                    negative: neg,
+                    decimal_places: dec_pl,
+                },
+            ));
+        }
+    }
+    (has_negative, has_decimal, out)
+}
+
+/// 逐字节解析的版本, 作为 [`retrave_blocks`] 的参照.
+/// 输入很短时比先分类快, `transcode` 用它解析字面量
+pub(crate) fn retrave_blocks_scalar(src: &[u8]) -> (bool, bool, Vec<Block>) {
+    let mut has_negative = false;
+    let mut has_decimal = false;
+
+    let s = src;
+    let n = s.len();
+    let mut out = Vec::with_capacity(n / 4);
     let mut idx = 0;
     const MAX: u64 = 999_999_999_999_999_999;
 
@@ -124,12 +222,13 @@ pub fn retrave_blocks(src: &[u8]) -> (bool, bool, Vec<Block>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn split_large() {
         let (has_negative, has_decimal, out) = retrave_blocks("99999999999999999988".as_bytes());
-        assert_eq!(has_negative, false);
-        assert_eq!(has_decimal, false);
+        assert!(!has_negative);
+        assert!(!has_decimal);
         assert_eq!(
             out,
             vec![
@@ -159,8 +258,8 @@ mod tests {
     fn split_decimal() {
         let (has_negative, has_decimal, out) =
             retrave_blocks("99999.999999999999999988".as_bytes());
-        assert_eq!(has_negative, false);
-        assert_eq!(has_decimal, true);
+        assert!(!has_negative);
+        assert!(has_decimal);
         assert_eq!(
             out,
             vec![
@@ -190,8 +289,8 @@ mod tests {
     fn mix() {
         let input = "  -12.050  007.8  +3.00  0.0  abc   ";
         let (has_negative, has_decimal, out) = retrave_blocks(input.as_bytes());
-        assert_eq!(has_negative, true);
-        assert_eq!(has_decimal, true);
+        assert!(has_negative);
+        assert!(has_decimal);
         assert_eq!(
             out,
             vec![
@@ -240,4 +339,45 @@ mod tests {
             ]
         );
     }
+
+    proptest! {
+        #[test]
+        fn matches_scalar(src in "[0-9 .\\-A]{0,300}") {
+            prop_assert_eq!(retrave_blocks(src.as_bytes()), retrave_blocks_scalar(src.as_bytes()));
+        }
+
+        #[test]
+        fn matches_scalar_any(src in prop::collection::vec(any::<u8>(), 0..300)) {
+            prop_assert_eq!(retrave_blocks(&src), retrave_blocks_scalar(&src));
+        }
+
+        #[test]
+        fn blocks_cover_their_text(src in "[0-9 .\\-A]{0,200}") {
+            let (has_negative, has_decimal, blocks) = retrave_blocks(src.as_bytes());
+            let mut end = 0;
+            for block in &blocks {
+                match block {
+                    Block::Space(start, size) => {
+                        prop_assert!(*start >= end && *size > 0);
+                        prop_assert!(src.as_bytes()[*start..start + size].iter().all(|&b| b == b' '));
+                        end = start + size;
+                    }
+                    Block::Numerical(start, size, number) => {
+                        prop_assert!(*start >= end);
+                        prop_assert!(number.decimal_places <= MAX_DECIMAL_PLACES);
+                        prop_assert!(!number.negative || has_negative);
+                        prop_assert!(number.decimal_places == 0 || has_decimal);
+                        let text = crate::all_ascii::format_number(
+                            number.base,
+                            number.negative,
+                            number.decimal_places as u8,
+                        );
+                        prop_assert_eq!(&src[*start..start + size], text);
+                        end = start + size;
+                    }
+                }
+            }
+            prop_assert!(end <= src.len());
+        }
+    }
 }
@@ -0,0 +1,282 @@
+//! 字节分类: 一次处理16/32个字节, 把空格和数字的位置记录成位图,
+//! `retrave_blocks` 用 `trailing_zeros` 找空格块和数字串的结尾。
+//!
+//! x86_64 上运行时检测 AVX2, 否则使用 SSE2; 其它平台每次处理8个字节 (SWAR)。
+
+const BLOCK: usize = 64;
+
+/// 不超过这个长度的输入, 位图存放在栈上
+const INLINE_WORDS: usize = 4;
+
+/// 输入中每个字节是否是空格/数字, 第 `i` 个字节对应 `words[i / 64]` 的第 `i % 64` 位
+pub(crate) struct Classes {
+    inline: [Word; INLINE_WORDS],
+    heap: Vec<Word>,
+    len: usize,
+}
+
+/// 64个字节的 (空格, 数字) 位图
+type Word = (u64, u64);
+
+impl Classes {
+    #[inline]
+    fn words(&self) -> &[Word] {
+        if self.len <= INLINE_WORDS * BLOCK {
+            &self.inline
+        } else {
+            &self.heap
+        }
+    }
+
+    /// 从 `index` 开始连续空格的结尾
+    #[inline]
+    pub fn space_end(&self, index: usize) -> usize {
+        self.run_end(index, |word| word.0)
+    }
+
+    /// 从 `index` 开始连续数字的结尾
+    #[inline]
+    pub fn digit_end(&self, index: usize) -> usize {
+        self.run_end(index, |word| word.1)
+    }
+
+    #[inline]
+    fn run_end(&self, mut index: usize, mask: impl Fn(&Word) -> u64) -> usize {
+        let words = self.words();
+        while index < self.len {
+            let rest = !mask(&words[index / BLOCK]) >> (index % BLOCK);
+            if rest != 0 {
+                return (index + rest.trailing_zeros() as usize).min(self.len);
+            }
+            index = (index / BLOCK + 1) * BLOCK;
+        }
+        self.len
+    }
+}
+
+pub(crate) fn classify(src: &[u8]) -> Classes {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::arch::is_x86_feature_detected!("avx2") {
+            // SAFETY: 已经检测过CPU支持AVX2
+            return unsafe { x86::classify_avx2(src) };
+        }
+        // SAFETY: x86_64 一定支持SSE2
+        return unsafe { x86::classify_sse2(src) };
+    }
+    #[allow(unreachable_code)]
+    classify_swar(src)
+}
+
+/// 按64字节分块, 最后不足64字节的部分补0 (0既不是空格也不是数字)
+#[inline(always)]
+fn classify_with(src: &[u8], block: impl Fn(&[u8; BLOCK]) -> Word) -> Classes {
+    let mut classes = Classes {
+        inline: [(0, 0); INLINE_WORDS],
+        heap: Vec::new(),
+        len: src.len(),
+    };
+    let words = if src.len() <= INLINE_WORDS * BLOCK {
+        &mut classes.inline[..src.len().div_ceil(BLOCK)]
+    } else {
+        classes.heap.resize(src.len().div_ceil(BLOCK), (0, 0));
+        &mut classes.heap[..]
+    };
+    let mut chunks = src.chunks_exact(BLOCK);
+    for (word, chunk) in words.iter_mut().zip(&mut chunks) {
+        *word = block(chunk.try_into().unwrap());
+    }
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        let mut tail = [0_u8; BLOCK];
+        tail[..rest.len()].copy_from_slice(rest);
+        words[words.len() - 1] = block(&tail);
+    }
+    classes
+}
+
+const ONES: u64 = 0x0101_0101_0101_0101;
+const LOW_7: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+const HIGH: u64 = 0x8080_8080_8080_8080;
+
+/// 每个字节的最高位收集成8位, 第 `k` 个字节对应第 `k` 位
+#[inline]
+fn movemask_swar(high_bits: u64) -> u64 {
+    ((high_bits >> 7).wrapping_mul(0x0102_0408_1020_4080)) >> 56
+}
+
+/// 等于 `b` 的字节最高位置1
+#[inline]
+fn eq_swar(word: u64, b: u8) -> u64 {
+    let x = word ^ (ONES * b as u64);
+    !(((x & LOW_7) + LOW_7) | x | LOW_7)
+}
+
+/// `'0'..='9'` 的字节最高位置1
+#[inline]
+fn digit_swar(word: u64) -> u64 {
+    let low = word & LOW_7;
+    let ge_0 = low + ONES * (0x80 - b'0') as u64;
+    let gt_9 = low + ONES * (0x80 - b'9' - 1) as u64;
+    ge_0 & !gt_9 & !word & HIGH
+}
+
+fn classify_swar(src: &[u8]) -> Classes {
+    classify_with(src, |block| {
+        let mut space = 0;
+        let mut digit = 0;
+        for (i, word) in block.chunks_exact(8).enumerate() {
+            let word = u64::from_le_bytes(word.try_into().unwrap());
+            space |= movemask_swar(eq_swar(word, b' ')) << (i * 8);
+            digit |= movemask_swar(digit_swar(word)) << (i * 8);
+        }
+        (space, digit)
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{BLOCK, Classes, classify_with};
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse2")]
+    pub(super) fn classify_sse2(src: &[u8]) -> Classes {
+        classify_with(src, |block| {
+            let space = _mm_set1_epi8(b' ' as i8);
+            let below_0 = _mm_set1_epi8(b'0' as i8 - 1);
+            let above_9 = _mm_set1_epi8(b'9' as i8 + 1);
+            let mut spaces = 0;
+            let mut digits = 0;
+            for i in 0..BLOCK / 16 {
+                // SAFETY: block有64个字节, 读取的16个字节在范围内
+                let v = unsafe { _mm_loadu_si128(block.as_ptr().add(i * 16).cast()) };
+                let is_space = _mm_movemask_epi8(_mm_cmpeq_epi8(v, space)) as u16 as u64;
+                // 有符号比较: 0x80以上的字节是负数, 不会被当成数字
+                let is_digit =
+                    _mm_and_si128(_mm_cmpgt_epi8(v, below_0), _mm_cmplt_epi8(v, above_9));
+                let is_digit = _mm_movemask_epi8(is_digit) as u16 as u64;
+                spaces |= is_space << (i * 16);
+                digits |= is_digit << (i * 16);
+            }
+            (spaces, digits)
+        })
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) fn classify_avx2(src: &[u8]) -> Classes {
+        classify_with(src, |block| {
+            let space = _mm256_set1_epi8(b' ' as i8);
+            let below_0 = _mm256_set1_epi8(b'0' as i8 - 1);
+            let above_9 = _mm256_set1_epi8(b'9' as i8 + 1);
+            let mut spaces = 0;
+            let mut digits = 0;
+            for i in 0..BLOCK / 32 {
+                // SAFETY: block有64个字节, 读取的32个字节在范围内
+                let v = unsafe { _mm256_loadu_si256(block.as_ptr().add(i * 32).cast()) };
+                let is_space = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, space)) as u32 as u64;
+                let is_digit =
+                    _mm256_and_si256(_mm256_cmpgt_epi8(v, below_0), _mm256_cmpgt_epi8(above_9, v));
+                let is_digit = _mm256_movemask_epi8(is_digit) as u32 as u64;
+                spaces |= is_space << (i * 32);
+                digits |= is_digit << (i * 32);
+            }
+            (spaces, digits)
+        })
+    }
+}
+
+/// 8个ASCII数字转换成整数, `digits[0]` 是最高位
+#[inline]
+fn parse_8_digits(digits: &[u8]) -> u64 {
+    let mut chunk = u64::from_le_bytes(digits[..8].try_into().unwrap()) - ONES * b'0' as u64;
+    chunk = (chunk.wrapping_mul(10) + (chunk >> 8)) & 0x00ff_00ff_00ff_00ff;
+    chunk = (chunk.wrapping_mul(100) + (chunk >> 16)) & 0x0000_ffff_0000_ffff;
+    (chunk.wrapping_mul(10000) + (chunk >> 32)) & 0xffff_ffff
+}
+
+/// 把 `digits` 接在 `acc` 后面, 调用者保证结果不会溢出
+#[inline]
+pub(crate) fn parse_digits(mut acc: u64, digits: &[u8]) -> u64 {
+    let mut chunks = digits.chunks_exact(8);
+    for chunk in &mut chunks {
+        acc = acc * 100_000_000 + parse_8_digits(chunk);
+    }
+    for &d in chunks.remainder() {
+        acc = acc * 10 + (d - b'0') as u64;
+    }
+    acc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn classify_scalar(src: &[u8]) -> Classes {
+        classify_with(src, |block| {
+            let mut space = 0;
+            let mut digit = 0;
+            for (i, &b) in block.iter().enumerate() {
+                space |= ((b == b' ') as u64) << i;
+                digit |= (b.is_ascii_digit() as u64) << i;
+            }
+            (space, digit)
+        })
+    }
+
+    fn assert_same(expected: &Classes, actual: &Classes) {
+        assert_eq!(expected.words(), actual.words());
+        assert_eq!(expected.len, actual.len);
+    }
+
+    #[test]
+    fn test_run_end() {
+        let src = b"  12 345          6  ";
+        let classes = classify(src);
+        assert_eq!(classes.space_end(0), 2);
+        assert_eq!(classes.digit_end(2), 4);
+        assert_eq!(classes.digit_end(0), 0);
+        assert_eq!(classes.space_end(8), 18);
+        assert_eq!(classes.space_end(19), src.len());
+        assert_eq!(classes.space_end(src.len()), src.len());
+
+        // 跨越64字节的边界
+        let src = format!("A{}B{}", " ".repeat(100), "9".repeat(200));
+        let classes = classify(src.as_bytes());
+        assert_eq!(classes.space_end(1), 101);
+        assert_eq!(classes.digit_end(102), 302);
+        assert_eq!(classes.digit_end(128), 302);
+    }
+
+    #[test]
+    fn test_parse_digits() {
+        assert_eq!(parse_8_digits(b"12345678"), 12_345_678);
+        assert_eq!(parse_8_digits(b"00000009"), 9);
+        assert_eq!(
+            parse_digits(0, b"999999999999999999"),
+            999_999_999_999_999_999
+        );
+        assert_eq!(parse_digits(12, b"0345"), 120_345);
+        assert_eq!(parse_digits(7, b""), 7);
+    }
+
+    proptest! {
+        #[test]
+        fn all_paths_agree(src in prop::collection::vec(any::<u8>(), 0..300)) {
+            let expected = classify_scalar(&src);
+            assert_same(&expected, &classify_swar(&src));
+            assert_same(&expected, &classify(&src));
+            #[cfg(target_arch = "x86_64")]
+            {
+                // SAFETY: x86_64 一定支持SSE2
+                assert_same(&expected, &unsafe { x86::classify_sse2(&src) });
+            }
+        }
+
+        #[test]
+        fn parse_digits_matches_str(digits in "[0-9]{0,18}") {
+            let expected = if digits.is_empty() { 0 } else { digits.parse::<u64>().unwrap() };
+            prop_assert_eq!(parse_digits(0, digits.as_bytes()), expected);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Compressed sizes of the files in `tests/corpus/` must not grow.
+//!
+//! The corpus has representative fixed-width, CSV-like, log-like and numeric-heavy
+//! records, one per line. When an encoder change makes the output smaller, lower the
+//! baseline in the same commit so the gain is kept.
+
+use zsan::{Compressor, decompress};
+
+/// 语料文件, 逐行压缩的总字节数, 以及整个文件经过哈夫曼编码后的字节数
+const BASELINE: &[(&str, usize, usize)] = &[
+    ("fixed_width", 19_625, 16_577),
+    ("csv", 19_345, 15_001),
+    ("log", 31_385, 24_144),
+    ("numeric", 23_121, 19_971),
+];
+
+fn corpus(name: &str) -> String {
+    let path = format!("{}/tests/corpus/{name}.txt", env!("CARGO_MANIFEST_DIR"));
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{path}: {e}"))
+}
+
+/// 逐行压缩, 同时检查能还原
+fn compressed_lines(text: &str, compressor: &Compressor) -> usize {
+    text.lines()
+        .map(|line| {
+            let mut out = Vec::new();
+            compressor.compress(line, &mut out);
+            let mut back = Vec::new();
+            decompress(&out, &mut back).unwrap();
+            assert_eq!(back, line.as_bytes());
+            out.len()
+        })
+        .sum()
+}
+
+#[test]
+fn compressed_size_does_not_regress() {
+    let mut sizes = Vec::new();
+    let mut regressions = Vec::new();
+    for &(name, lines_baseline, entropy_baseline) in BASELINE {
+        let text = corpus(name);
+        let lines = compressed_lines(&text, &Compressor::default());
+        let mut entropy = Vec::new();
+        Compressor::builder()
+            .entropy(true)
+            .build()
+            .compress(&text, &mut entropy);
+        sizes.push(format!(
+            "{name}: {} bytes, {lines} bytes by line ({:.1}%), {} bytes with huffman ({:.1}%)",
+            text.len(),
+            lines as f64 * 100.0 / text.len() as f64,
+            entropy.len(),
+            entropy.len() as f64 * 100.0 / text.len() as f64,
+        ));
+        if lines > lines_baseline {
+            regressions.push(format!("{name} by line: {lines} > {lines_baseline}"));
+        }
+        if entropy.len() > entropy_baseline {
+            regressions.push(format!(
+                "{name} with huffman: {} > {entropy_baseline}",
+                entropy.len()
+            ));
+        }
+    }
+    assert!(
+        regressions.is_empty(),
+        "{regressions:#?}\nsizes:\n{}",
+        sizes.join("\n")
+    );
+}
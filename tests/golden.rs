@@ -0,0 +1,142 @@
+//! Frozen outputs of format version 0.
+//!
+//! These bytes may already be persisted, so they must never change: every release
+//! has to decode them to the same text, and the default encoder has to keep
+//! producing them. Add new cases, never edit existing ones.
+
+use zsan::{ZsanError, canonicalize, compress, compress_entropy, decompress};
+
+const GOLDEN: &[(&str, &str)] = &[
+    ("A  B", "40418242"),
+    ("A   123  B", "404183fb038242"),
+    ("A   123  B-1234", "424183db078242f24d"),
+    ("A   -1.23  B0.1-234", "434183f27b8242d101ea1d"),
+    ("A   123  B0.1234", "414183cb0f8242e8e904"),
+    ("123 ", "40fb0320"),
+    ("30", "40de"),
+    ("ABCDEFG", "4841424344454647"),
+    (
+        "6224      ABC20200902       1312       1145       7802       5411          17800          90532                                           1109.2049 ",
+        "41c88a0686414243ceb88f9a0187c8a40187c98f0187cacf0787cba4058ac8b1118accb458abe9a8c0d20220",
+    ),
+    (
+        "9951      EFG0990     132230     132280     102230     192230          4         46     5938.6 561969.6111",
+        "41cfdb098645464730ce7b85ce90810185c897810185ceea6385cedcbb018a3489343685e2fde70120e9f7c1ebbb0a",
+    ),
+];
+
+/// `compress_entropy` of `"EFG   12345  -1.5\n"` repeated 20 times.
+const GOLDEN_ENTROPY: &str = "47dc010a0a0c0f454647828387c9f1444444333330def2d58995bde5ab132b7bcb562656f796ac4cadef2d58995bde5ab132b7bcb562656f796ac4cadef2d58995bde5ab132b7bcb562656f796ac4cadef2d58995bde5ab132b7bcb562656f796ac4cadef2d58995bde5ab132b7bcb562656f796ac4ca0";
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn decompress_to_string(input: &[u8]) -> Result<String, ZsanError> {
+    let mut out = Vec::new();
+    decompress(input, &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn golden_decode() {
+    for (text, hex) in GOLDEN {
+        assert_eq!(decompress_to_string(&from_hex(hex)).unwrap(), *text);
+    }
+    assert_eq!(
+        decompress_to_string(&from_hex(GOLDEN_ENTROPY)).unwrap(),
+        "EFG   12345  -1.5\n".repeat(20)
+    );
+}
+
+#[test]
+fn golden_encode() {
+    for (text, hex) in GOLDEN {
+        let mut out = Vec::new();
+        compress(text, &mut out);
+        assert_eq!(out, from_hex(hex), "{text:?}");
+    }
+    let mut out = Vec::new();
+    compress_entropy(&"EFG   12345  -1.5\n".repeat(20), &mut out);
+    assert_eq!(out, from_hex(GOLDEN_ENTROPY));
+}
+
+/// `compress` 的输出就是规范形式
+#[test]
+fn golden_canonical() {
+    for (text, hex) in GOLDEN {
+        let mut out = Vec::new();
+        canonicalize(&from_hex(hex), &mut out).unwrap();
+        assert_eq!(out, from_hex(hex), "{text:?}");
+    }
+    let mut out = Vec::new();
+    canonicalize(&from_hex(GOLDEN_ENTROPY), &mut out).unwrap();
+    let mut expected = Vec::new();
+    compress(&"EFG   12345  -1.5\n".repeat(20), &mut expected);
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn unknown_versions_are_refused() {
+    for (_, hex) in GOLDEN {
+        let mut input = from_hex(hex);
+        for version in 1..4 {
+            input[0] = (input[0] & 0b_1100_1111) | (version << 4);
+            assert_eq!(
+                decompress_to_string(&input),
+                Err(ZsanError::UnsupportedVersion(version))
+            );
+        }
+    }
+}
+
+#[test]
+fn unknown_features_are_refused() {
+    let mut input = from_hex(GOLDEN[1].1);
+    input[0] |= 0b_1000_0000;
+    input.insert(1, 0b_0100_0000);
+    assert_eq!(
+        decompress_to_string(&input),
+        Err(ZsanError::UnsupportedFeature(6))
+    );
+
+    // 空的扩展字节不影响解码
+    input[1] = 0;
+    assert_eq!(decompress_to_string(&input).unwrap(), GOLDEN[1].0);
+}
+
+#[test]
+fn invalid_header() {
+    assert_eq!(
+        decompress_to_string(b"1"),
+        Err(ZsanError::InvalidHeader(b'1'))
+    );
+    assert!(
+        ZsanError::UnsupportedVersion(2)
+            .to_string()
+            .contains("version 2")
+    );
+}
+
+#[test]
+fn invalid_tokens() {
+    // 数字token在中间截断
+    let mut input = Vec::new();
+    compress("A 12345678", &mut input);
+    input.pop();
+    let mut out = b"kept".to_vec();
+    assert_eq!(
+        decompress(&input, &mut out),
+        Err(ZsanError::InvalidToken(2))
+    );
+    assert_eq!(out, b"kept");
+
+    // 0x7f既不是字面量也不是token
+    assert_eq!(
+        decompress_to_string(&[input[0], b'A', 0x7f]),
+        Err(ZsanError::InvalidToken(1))
+    );
+}
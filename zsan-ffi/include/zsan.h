@@ -0,0 +1,70 @@
+/*
+ * C interface of zsan, built as `libzsan.a` / `libzsan.so` by
+ * `cargo build --release -p zsan-ffi`.
+ *
+ * Linking the static library on Linux also needs `-lpthread -ldl -lm`.
+ *
+ * All buffers belong to the caller. `src` and `dst` may be NULL when their length
+ * is 0. Every function returns ZSAN_OK or one of the negative error codes below and
+ * never aborts the process.
+ */
+
+#ifndef ZSAN_H
+#define ZSAN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ZSAN_OK (0)
+/* A required pointer is NULL. */
+#define ZSAN_ERROR_NULL_POINTER (-1)
+/* `dst_cap` is too small, `*dst_len` is set to the required size. */
+#define ZSAN_ERROR_BUFFER_TOO_SMALL (-2)
+/* The compressed data ends in the middle of the header or a token. */
+#define ZSAN_ERROR_TRUNCATED (-3)
+/* The first byte is not a zsan header. */
+#define ZSAN_ERROR_INVALID_HEADER (-4)
+/* The data was written by a newer format version. */
+#define ZSAN_ERROR_UNSUPPORTED_VERSION (-5)
+/* The data uses an extension feature this version does not know. */
+#define ZSAN_ERROR_UNSUPPORTED_FEATURE (-6)
+/* A token of the compressed data is malformed. */
+#define ZSAN_ERROR_INVALID_TOKEN (-7)
+/* The Huffman coded token stream is malformed. */
+#define ZSAN_ERROR_INVALID_ENTROPY_STREAM (-8)
+/* The checksum stored in the data does not match the decoded text. */
+#define ZSAN_ERROR_CHECKSUM_MISMATCH (-9)
+/* Internal error, please report it. */
+#define ZSAN_ERROR_PANIC (-100)
+
+/* Upper bound of the output of zsan_compress for `src_len` input bytes. */
+size_t zsan_max_compressed_len(size_t src_len);
+
+/*
+ * Compress `src_len` bytes at `src` into `dst` with the default options.
+ *
+ * On success `*dst_len` is the compressed size. A `dst_cap` of at least
+ * zsan_max_compressed_len(src_len) is always enough.
+ */
+int zsan_compress(const uint8_t *src, size_t src_len, uint8_t *dst, size_t dst_cap,
+                  size_t *dst_len);
+
+/*
+ * Decompress `src_len` bytes at `src` into `dst`.
+ *
+ * On success `*dst_len` is the size of the text. If `dst_cap` is too small,
+ * ZSAN_ERROR_BUFFER_TOO_SMALL is returned and `*dst_len` is the required size, so
+ * the call can be repeated with a larger buffer.
+ */
+int zsan_decompress(const uint8_t *src, size_t src_len, uint8_t *dst, size_t dst_cap,
+                    size_t *dst_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ZSAN_H */
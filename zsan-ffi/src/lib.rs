@@ -0,0 +1,214 @@
+//! C ABI of zsan, declared in `include/zsan.h`.
+//!
+//! All buffers belong to the caller. Functions never unwind into C: errors, including
+//! panics, are returned as the negative `ZSAN_ERROR_*` codes of the header.
+
+use std::ffi::c_int;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::slice;
+
+use zsan_core::{Compressor, ZsanError};
+
+// 与 include/zsan.h 保持一致, 由 tests::test_header 检查
+const ZSAN_OK: c_int = 0;
+const ZSAN_ERROR_NULL_POINTER: c_int = -1;
+const ZSAN_ERROR_BUFFER_TOO_SMALL: c_int = -2;
+const ZSAN_ERROR_TRUNCATED: c_int = -3;
+const ZSAN_ERROR_INVALID_HEADER: c_int = -4;
+const ZSAN_ERROR_UNSUPPORTED_VERSION: c_int = -5;
+const ZSAN_ERROR_UNSUPPORTED_FEATURE: c_int = -6;
+const ZSAN_ERROR_INVALID_TOKEN: c_int = -7;
+const ZSAN_ERROR_INVALID_ENTROPY_STREAM: c_int = -8;
+const ZSAN_ERROR_CHECKSUM_MISMATCH: c_int = -9;
+const ZSAN_ERROR_PANIC: c_int = -100;
+
+fn error_code(error: &ZsanError) -> c_int {
+    match error {
+        ZsanError::Truncated => ZSAN_ERROR_TRUNCATED,
+        ZsanError::InvalidHeader(_) => ZSAN_ERROR_INVALID_HEADER,
+        ZsanError::UnsupportedVersion(_) => ZSAN_ERROR_UNSUPPORTED_VERSION,
+        ZsanError::UnsupportedFeature(_) => ZSAN_ERROR_UNSUPPORTED_FEATURE,
+        ZsanError::InvalidToken(_) => ZSAN_ERROR_INVALID_TOKEN,
+        ZsanError::InvalidEntropyStream => ZSAN_ERROR_INVALID_ENTROPY_STREAM,
+        ZsanError::ChecksumMismatch { .. } => ZSAN_ERROR_CHECKSUM_MISMATCH,
+    }
+}
+
+/// 长度为0时允许空指针
+unsafe fn input<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        Some(&[])
+    } else if ptr.is_null() {
+        None
+    } else {
+        // SAFETY: 调用者保证 ptr 指向 len 个可读的字节
+        Some(unsafe { slice::from_raw_parts(ptr, len) })
+    }
+}
+
+/// 把 `produce` 的结果拷贝到调用者的缓冲区, `*dst_len` 总是设置为需要的长度
+unsafe fn write_output(
+    dst: *mut u8,
+    dst_cap: usize,
+    dst_len: *mut usize,
+    produce: impl FnOnce(&mut Vec<u8>) -> c_int,
+) -> c_int {
+    if dst_len.is_null() || (dst.is_null() && dst_cap > 0) {
+        return ZSAN_ERROR_NULL_POINTER;
+    }
+    let mut out = Vec::new();
+    let code = match catch_unwind(AssertUnwindSafe(|| produce(&mut out))) {
+        Ok(code) => code,
+        Err(_) => ZSAN_ERROR_PANIC,
+    };
+    if code != ZSAN_OK {
+        return code;
+    }
+    // SAFETY: dst_len 不是空指针, 调用者保证可写
+    unsafe { *dst_len = out.len() };
+    if out.len() > dst_cap {
+        return ZSAN_ERROR_BUFFER_TOO_SMALL;
+    }
+    if !out.is_empty() {
+        // SAFETY: dst 有 dst_cap >= out.len() 个可写的字节, 且与 out 不重叠
+        unsafe { std::ptr::copy_nonoverlapping(out.as_ptr(), dst, out.len()) };
+    }
+    ZSAN_OK
+}
+
+/// Upper bound of the output of `zsan_compress` for `src_len` input bytes.
+#[unsafe(no_mangle)]
+pub extern "C" fn zsan_max_compressed_len(src_len: usize) -> usize {
+    src_len.saturating_add(1)
+}
+
+/// Compress `src_len` bytes at `src` into `dst`, see `include/zsan.h`.
+///
+/// # Safety
+///
+/// `src` must point to `src_len` readable bytes, `dst` to `dst_cap` writable bytes
+/// and `dst_len` to a writable `size_t`. `src` and `dst` may be null when their
+/// length is 0.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zsan_compress(
+    src: *const u8,
+    src_len: usize,
+    dst: *mut u8,
+    dst_cap: usize,
+    dst_len: *mut usize,
+) -> c_int {
+    // SAFETY: 见函数文档
+    let Some(src) = (unsafe { input(src, src_len) }) else {
+        return ZSAN_ERROR_NULL_POINTER;
+    };
+    // SAFETY: 见函数文档
+    unsafe {
+        write_output(dst, dst_cap, dst_len, |out| {
+            Compressor::default().compress_bytes(src, out);
+            ZSAN_OK
+        })
+    }
+}
+
+/// Decompress `src_len` bytes at `src` into `dst`, see `include/zsan.h`.
+///
+/// # Safety
+///
+/// Same as [`zsan_compress`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zsan_decompress(
+    src: *const u8,
+    src_len: usize,
+    dst: *mut u8,
+    dst_cap: usize,
+    dst_len: *mut usize,
+) -> c_int {
+    // SAFETY: 见函数文档
+    let Some(src) = (unsafe { input(src, src_len) }) else {
+        return ZSAN_ERROR_NULL_POINTER;
+    };
+    // SAFETY: 见函数文档
+    unsafe {
+        write_output(dst, dst_cap, dst_len, |out| {
+            zsan_core::decompress(src, out).map_or_else(|e| error_code(&e), |_| ZSAN_OK)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        let header = include_str!("../include/zsan.h");
+        for (name, value) in [
+            ("ZSAN_OK", ZSAN_OK),
+            ("ZSAN_ERROR_NULL_POINTER", ZSAN_ERROR_NULL_POINTER),
+            ("ZSAN_ERROR_BUFFER_TOO_SMALL", ZSAN_ERROR_BUFFER_TOO_SMALL),
+            ("ZSAN_ERROR_TRUNCATED", ZSAN_ERROR_TRUNCATED),
+            ("ZSAN_ERROR_INVALID_HEADER", ZSAN_ERROR_INVALID_HEADER),
+            (
+                "ZSAN_ERROR_UNSUPPORTED_VERSION",
+                ZSAN_ERROR_UNSUPPORTED_VERSION,
+            ),
+            (
+                "ZSAN_ERROR_UNSUPPORTED_FEATURE",
+                ZSAN_ERROR_UNSUPPORTED_FEATURE,
+            ),
+            ("ZSAN_ERROR_INVALID_TOKEN", ZSAN_ERROR_INVALID_TOKEN),
+            (
+                "ZSAN_ERROR_INVALID_ENTROPY_STREAM",
+                ZSAN_ERROR_INVALID_ENTROPY_STREAM,
+            ),
+            ("ZSAN_ERROR_CHECKSUM_MISMATCH", ZSAN_ERROR_CHECKSUM_MISMATCH),
+            ("ZSAN_ERROR_PANIC", ZSAN_ERROR_PANIC),
+        ] {
+            let define = format!("#define {name} ({value})");
+            assert!(header.contains(&define), "missing `{define}`");
+        }
+    }
+
+    #[test]
+    fn test_buffers() {
+        let src = b"6224      ABC20200902       1312";
+        let mut len = 0;
+        // 只查询需要的长度
+        let code =
+            unsafe { zsan_compress(src.as_ptr(), src.len(), std::ptr::null_mut(), 0, &mut len) };
+        assert_eq!(code, ZSAN_ERROR_BUFFER_TOO_SMALL);
+        assert!(len > 0 && len <= zsan_max_compressed_len(src.len()));
+
+        let mut compressed = vec![0; len];
+        let code = unsafe {
+            zsan_compress(
+                src.as_ptr(),
+                src.len(),
+                compressed.as_mut_ptr(),
+                len,
+                &mut len,
+            )
+        };
+        assert_eq!(code, ZSAN_OK);
+
+        let mut text = vec![0; src.len()];
+        let code = unsafe {
+            zsan_decompress(
+                compressed.as_ptr(),
+                len,
+                text.as_mut_ptr(),
+                text.len(),
+                &mut len,
+            )
+        };
+        assert_eq!(code, ZSAN_OK);
+        assert_eq!(&text[..len], src);
+
+        let code = unsafe { zsan_decompress(b"1".as_ptr(), 1, text.as_mut_ptr(), 0, &mut len) };
+        assert_eq!(code, ZSAN_ERROR_INVALID_HEADER);
+        let code = unsafe { zsan_decompress(std::ptr::null(), 1, text.as_mut_ptr(), 0, &mut len) };
+        assert_eq!(code, ZSAN_ERROR_NULL_POINTER);
+        let code = unsafe { zsan_compress(std::ptr::null(), 0, std::ptr::null_mut(), 0, &mut len) };
+        assert_eq!((code, len), (ZSAN_OK, 0));
+    }
+}
@@ -0,0 +1,62 @@
+/* Compiled and run by tests/c_api.rs. */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "zsan.h"
+
+#define CHECK(cond)                                                        \
+    do {                                                                   \
+        if (!(cond)) {                                                     \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                \
+            return 1;                                                      \
+        }                                                                  \
+    } while (0)
+
+static const char *INPUTS[] = {
+    "6224      ABC20200902       1312       1145       7802       5411          17800          90532                                           1109.2049 ",
+    "9951      EFG0990     132230     132280     102230     192230          4         46     5938.6 561969.6111",
+    "",
+};
+
+static int round_trip(const char *input) {
+    size_t src_len = strlen(input);
+    size_t cap = zsan_max_compressed_len(src_len);
+    uint8_t *compressed = malloc(cap + 1);
+    size_t compressed_len = 0;
+    CHECK(compressed != NULL);
+    CHECK(zsan_compress((const uint8_t *)input, src_len, compressed, cap, &compressed_len) == ZSAN_OK);
+    CHECK(compressed_len <= cap);
+
+    /* 先查询需要的长度, 再解压 */
+    size_t text_len = 0;
+    CHECK(zsan_decompress(compressed, compressed_len, NULL, 0, &text_len) ==
+          (src_len == 0 ? ZSAN_OK : ZSAN_ERROR_BUFFER_TOO_SMALL));
+    CHECK(text_len == src_len);
+    uint8_t *text = malloc(text_len + 1);
+    CHECK(text != NULL);
+    CHECK(zsan_decompress(compressed, compressed_len, text, text_len, &text_len) == ZSAN_OK);
+    CHECK(text_len == src_len && memcmp(text, input, src_len) == 0);
+    free(compressed);
+    free(text);
+    return 0;
+}
+
+int main(void) {
+    for (size_t i = 0; i < sizeof(INPUTS) / sizeof(INPUTS[0]); i++) {
+        if (round_trip(INPUTS[i]) != 0) {
+            return 1;
+        }
+    }
+
+    size_t len = 0;
+    uint8_t out[16];
+    CHECK(zsan_decompress((const uint8_t *)"1", 1, out, sizeof(out), &len) == ZSAN_ERROR_INVALID_HEADER);
+    CHECK(zsan_decompress(NULL, 4, out, sizeof(out), &len) == ZSAN_ERROR_NULL_POINTER);
+    CHECK(zsan_compress((const uint8_t *)"A", 1, out, sizeof(out), NULL) == ZSAN_ERROR_NULL_POINTER);
+
+    puts("ok");
+    return 0;
+}
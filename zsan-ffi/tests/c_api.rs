@@ -0,0 +1,52 @@
+//! Compiles `tests/c/round_trip.c` against the static library and runs it.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_round_trip() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let tmp_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    // `cargo test` 不构建staticlib, 需要单独构建。使用单独的目录,
+    // 不覆盖 `cargo test --all-features` 的doctest链接的rlib
+    let target_dir = tmp_dir.join("c_api");
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--manifest-path"])
+        .arg(manifest_dir.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "building the static library failed");
+    let library = target_dir.join("debug/libzsan.a");
+    assert!(library.exists(), "{} not found", library.display());
+
+    let exe = tmp_dir.join("zsan_c_round_trip");
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let status = match Command::new(&compiler)
+        .arg(manifest_dir.join("tests/c/round_trip.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .args(["-std=c99", "-Wall", "-Werror", "-o"])
+        .arg(&exe)
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            eprintln!("skipping the C test, `{compiler}` not found");
+            return;
+        }
+        Err(e) => panic!("failed to run `{compiler}`: {e}"),
+    };
+    assert!(status.success(), "compiling the C test failed");
+
+    let output = Command::new(&exe).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}